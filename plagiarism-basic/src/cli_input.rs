@@ -1,94 +1,719 @@
-use clap::{App, Arg};
-use plagiarismbasic_lib::{AppSettings, Metric};
+use crate::config::{load_config, Config};
+use crate::EXIT_ERROR;
+use clap::{App, AppSettings as ClapSettings, Arg, ArgMatches, ErrorKind, SubCommand};
+use plagiarismbasic_lib::{AppSettings, CompareView, Language, Query};
+use std::collections::HashMap;
+use std::env;
+use std::process;
 
-pub fn get_cli_input() -> AppSettings {
+/// What the program was asked to do
+pub enum CliCommand {
+    /// Check submissions for plagiarism
+    Check(Box<AppSettings>),
+    /// Save trusted and archived texts to an index file
+    Index(Box<AppSettings>),
+    /// Report the results saved in an incremental state file again
+    Report(String, Box<AppSettings>),
+    /// Compare two files with each other
+    Compare {
+        file1: String,
+        file2: String,
+        view: CompareView,
+        width: usize,
+        appsettings: Box<AppSettings>,
+    },
+    /// Look something up in a results database
+    Query(String, Query),
+}
+
+/// Options of the check subcommand, which can also be given without a subcommand
+const CHECK_ARGS: &[&str] = &[
+    "untrusted-directory",
+    "trusted-directory",
+    "archive-directory",
+    "index",
+    "save-index",
+    "incremental",
+    "ignore-directory",
+    "ignore-metric",
+    "ignore-similarity",
+    "exclude-stopwords",
+    "boilerplate",
+    "dedup-sources",
+    "manifest",
+    "section",
+    "groups",
+    "recursive",
+    "submission-dirs",
+    "glob",
+    "symlinks",
+    "hidden",
+    "metric",
+    "sensitivity",
+    "similarity",
+    "language",
+    "cross-language",
+    "split-comments",
+    "comments-sensitivity",
+    "comments-similarity",
+    "output-cli",
+    "output-html",
+    "open-html",
+    "quiet",
+    "fail-threshold",
+    "results-db",
+    "config",
+    "profile",
+];
+
+/// Options of the index subcommand
+const INDEX_ARGS: &[&str] = &[
+    "trusted-directory",
+    "archive-directory",
+    "index",
+    "recursive",
+    "submission-dirs",
+    "glob",
+    "symlinks",
+    "hidden",
+    "sensitivity",
+    "language",
+    "split-comments",
+    "comments-sensitivity",
+    "config",
+    "profile",
+];
+
+/// Options of the report subcommand
+const REPORT_ARGS: &[&str] = &[
+    "manifest",
+    "section",
+    "output-cli",
+    "output-html",
+    "open-html",
+    "quiet",
+    "fail-threshold",
+    "config",
+    "profile",
+];
+
+/// Options of the compare subcommand
+const COMPARE_ARGS: &[&str] = &[
+    "metric",
+    "sensitivity",
+    "similarity",
+    "exclude-stopwords",
+    "language",
+    "cross-language",
+    "split-comments",
+    "comments-sensitivity",
+    "comments-similarity",
+    "config",
+    "profile",
+];
+
+/// Defines an option shared by several subcommands
+fn arg(name: &str) -> Arg<'static, 'static> {
+    match name {
+        "untrusted-directory" => Arg::with_name("untrusted-directory")
+            .short("u")
+            .long("untrusted")
+            .help("Sets the directory containing untrusted text files. Each file will be treated as a separate submission by a separate person.")
+            .takes_value(true),
+        "trusted-directory" => Arg::with_name("trusted-directory")
+            .short("t")
+            .long("trusted")
+            .help("Sets the directory containing trusted text files. Each file will be treated as a separate possible plagiarism source text.")
+            .takes_value(true),
+        "archive-directory" => Arg::with_name("archive-directory")
+            .long("archive")
+            .help("Sets the directory containing submissions from past terms, with one subdirectory per term (e.g. 2023S1). Untrusted submissions are checked against them, but they are not compared to each other.")
+            .takes_value(true),
+        "index" => Arg::with_name("index")
+            .long("index")
+            .help("Sets an index file of trusted and archived texts saved by a previous run (with --save-index) to check against as well as the trusted directory. Must have been built with the same sensitivity values and --comments setting.")
+            .takes_value(true),
+        "save-index" => Arg::with_name("save-index")
+            .long("save-index")
+            .help("Saves the trusted and archived texts (including any from --index) to an index file, so later runs can check against them without reading them again. Can be the same file as --index to add to it.")
+            .takes_value(true),
+        "incremental" => Arg::with_name("incremental")
+            .long("incremental")
+            .help("Sets a state file to save the texts and results of this run to. If the file already exists, only untrusted submissions that are new or have changed since it was saved are checked, and the results are merged with the saved ones. The trusted, archive and ignore directories are only read on the first run.")
+            .takes_value(true),
+        "ignore-directory" => Arg::with_name("ignore-directory")
+            .short("i")
+            .long("ignore")
+            .help("Sets the directory containing text files with content to be ignored from plagiarism checks.")
+            .takes_value(true),
+        "ignore-metric" => Arg::with_name("ignore-metric")
+            .long("ignore-metric")
+            .help("Sets the metric used to match text against the ignored texts, so that e.g. near-copies of an assignment prompt are ignored too with lev. Defaults to equal, since lev compares every fragment with every ignored fragment, which is slow with a large ignore directory")
+            .takes_value(true)
+            .possible_values(&["equal", "lev"]),
+        "ignore-similarity" => Arg::with_name("ignore-similarity")
+            .long("ignore-similarity")
+            .help("Sets the threshold value used to match text against the ignored texts. Defaults to the similarity value")
+            .takes_value(true),
+        "exclude-stopwords" => Arg::with_name("exclude-stopwords")
+            .long("exclude-stopwords")
+            .help("If common English words (such as \"the\" and \"of\") should be left out of the adjusted plagiarism percentages, which already leave out ignored text"),
+        "boilerplate" => Arg::with_name("boilerplate")
+            .long("boilerplate")
+            .help("Ignores text found in more than this fraction (0 to 1) of untrusted submissions, such as the assignment prompt or code scaffolding. The ignored text is listed in the report")
+            .takes_value(true),
+        "dedup-sources" => Arg::with_name("dedup-sources")
+            .long("dedup-sources")
+            .help("If trusted sources should be compared to each other first, so that text found in several overlapping sources is only reported against the one that covers the most of each untrusted text (or the earliest, by manifest timestamp)"),
+        "manifest" => Arg::with_name("manifest")
+            .long("manifest")
+            .help("Sets a CSV or JSON file listing the student ID, name, group, section and timestamp of each untrusted submission. Columns (or fields) are file, student_id, name, group, section and timestamp, where file is the name of the submission in the untrusted directory")
+            .takes_value(true),
+        "section" => Arg::with_name("section")
+            .long("section")
+            .help("Only reports results involving a submission from this section of the manifest")
+            .takes_value(true),
+        "groups" => Arg::with_name("groups")
+            .long("groups")
+            .help("Sets how submissions from members of the same group in the manifest are compared. skip doesn't compare them to each other, label compares them but marks the results, and merge combines each group's submissions so that groups are compared as units")
+            .takes_value(true)
+            .possible_values(&["skip", "label", "merge"])
+            .default_value("skip"),
+        "recursive" => Arg::with_name("recursive")
+            .short("r")
+            .long("recursive")
+            .help("If files in subdirectories of the untrusted, trusted and ignore directories should also be read"),
+        "submission-dirs" => Arg::with_name("submission-dirs")
+            .long("submission-dirs")
+            .help("If each top-level subdirectory of the untrusted and trusted directories should be treated as one submission made up of all the files inside it"),
+        "glob" => Arg::with_name("glob")
+            .short("g")
+            .long("glob")
+            .help("Only reads files whose path (relative to the directory) matches this glob pattern, e.g. **/*.txt. Patterns starting with ! exclude files instead, e.g. !**/node_modules/**. Can be given multiple times")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        "symlinks" => Arg::with_name("symlinks")
+            .long("symlinks")
+            .help("Sets whether symbolic links are followed or skipped when reading directories")
+            .takes_value(true)
+            .possible_values(&["follow", "skip"])
+            .default_value("follow"),
+        "hidden" => Arg::with_name("hidden")
+            .long("hidden")
+            .help("If hidden files and directories (names starting with a dot) should also be read"),
+        "metric" => Arg::with_name("metric")
+            .short("m")
+            .long("metric")
+            .help("Sets the metric (function) used for similarity testing. Equal checks that both strings are equal, and lev uses the Levenshtein distance")
+            .takes_value(true)
+            .possible_values(&["equal", "lev"]),
+        "sensitivity" => Arg::with_name("sensitivity")
+            .short("n")
+            .long("sensitivity")
+            .help("Sets the number of words required to form a unit of plagiarism checking")
+            .takes_value(true),
+        "similarity" => Arg::with_name("similarity")
+            .short("s")
+            .long("similarity")
+            .help("Sets the threshold value for plagiarism to be detected by a chosen metric")
+            .takes_value(true),
+        "language" => Arg::with_name("language")
+            .short("l")
+            .long("language")
+            .help("Treats every file as being written in this language instead of detecting it from the file extension and contents")
+            .takes_value(true)
+            .possible_values(&Language::NAMES),
+        "cross-language" => Arg::with_name("cross-language")
+            .long("cross-language")
+            .help("If files written in different languages should still be compared against each other"),
+        "split-comments" => Arg::with_name("split-comments")
+            .long("comments")
+            .help("If comments and string literals in code files should be compared separately from the code itself"),
+        "comments-sensitivity" => Arg::with_name("comments-sensitivity")
+            .long("comments-sensitivity")
+            .help("Sets the number of words required to form a unit of plagiarism checking in comments. Defaults to the sensitivity value")
+            .takes_value(true),
+        "comments-similarity" => Arg::with_name("comments-similarity")
+            .long("comments-similarity")
+            .help("Sets the threshold value for plagiarism to be detected in comments. Defaults to the similarity value")
+            .takes_value(true),
+        "output-cli" => Arg::with_name("output-cli")
+            .long("cli")
+            .help("If the results should be printed to the command line"),
+        "output-html" => Arg::with_name("output-html")
+            .long("html")
+            .help("If the results should be printed to a HTML file"),
+        "open-html" => Arg::with_name("open-html")
+            .long("openhtml")
+            .help("If the HTML file should be opened automatically after writing"),
+        "quiet" => Arg::with_name("quiet")
+            .short("q")
+            .long("quiet")
+            .help("Only prints a one-line summary of the findings instead of the results. The exit code is 0 if nothing was found, 1 if there are findings and 2 on errors either way"),
+        "fail-threshold" => Arg::with_name("fail-threshold")
+            .long("fail-threshold")
+            .help("Sets the percentage (0 to 100) of either text that has to be matching text for a result to count as a finding in the exit code and summary. Defaults to 0, so that every result counts")
+            .takes_value(true),
+        "results-db" => Arg::with_name("results-db")
+            .long("results-db")
+            .help("Stores the run, its settings, the submissions and all results (with the location of each match) in this SQLite database, which is created if it doesn't exist. Use the query subcommand to look through it later")
+            .takes_value(true),
+        "config" => Arg::with_name("config")
+            .long("config")
+            .help("Reads settings from this TOML config file instead of ./plagiarism-basic.toml. Settings in the user-level config file (e.g. ~/.config/plagiarism-basic/config.toml) are read first either way, and options given on the command line override both")
+            .takes_value(true),
+        "profile" => Arg::with_name("profile")
+            .long("profile")
+            .help("Uses the settings of this profile from the config files (e.g. [profiles.essay]) on top of their other settings")
+            .takes_value(true),
+        _ => panic!("Unknown option {}!", name),
+    }
+}
+
+/// Flags that can be turned off on the command line (e.g. when a config file turns
+///     them on), as (flag, option turning it off)
+const NEGATABLE_FLAGS: &[(&str, &str)] = &[
+    ("exclude-stopwords", "no-exclude-stopwords"),
+    ("dedup-sources", "no-dedup-sources"),
+    ("recursive", "no-recursive"),
+    ("submission-dirs", "no-submission-dirs"),
+    ("hidden", "no-hidden"),
+    ("cross-language", "no-cross-language"),
+    ("split-comments", "no-comments"),
+    ("output-cli", "no-cli"),
+    ("output-html", "no-html"),
+    ("open-html", "no-openhtml"),
+    ("quiet", "no-quiet"),
+];
+
+/// Defines the options with the given names, along with the option turning off
+///     each of them that is a flag. Those are left out of the help to keep it short.
+fn args(names: &[&str]) -> Vec<Arg<'static, 'static>> {
+    let mut args: Vec<Arg<'static, 'static>> = Vec::new();
+    for name in names {
+        args.push(arg(name));
+        if let Some((flag, negation)) = NEGATABLE_FLAGS.iter().find(|(flag, _)| flag == name) {
+            args.push(
+                Arg::with_name(negation)
+                    .long(negation)
+                    .hidden(true)
+                    .overrides_with(flag),
+            );
+        }
+    }
+    args
+}
+
+/// Explains the options left out of the help by args
+const NEGATION_HELP: &str =
+    "Flags can be turned off with --no-<flag> (e.g. --no-html), such as when a config file turns them on.";
+
+pub fn get_cli_input() -> CliCommand {
     let app = App::new("Basic Plagiarism Checker")
         .about("Checks for plagiarism using very basic metrics between different text files")
         .author("Sriram Sami (@frizensami on GitHub)")
-        .arg(Arg::with_name("untrusted-directory")
-                .short("u")
-                .long("untrusted")
-                .help("Sets the directory containing untrusted text files. Each file will be treated as a separate submission by a separate person.")
-                .takes_value(true)
-                .required(true))
-        .arg(Arg::with_name("trusted-directory")
-                .short("t")
-                .long("trusted")
-                .help("Sets the directory containing trusted text files. Each file will be treated as a separate possible plagiarism source text.")
-                .takes_value(true))
-        .arg(Arg::with_name("ignore-directory")
-                .short("i")
-                .long("ignore")
-                .help("Sets the directory containing text files with content to be ignored from plagiarism checks.")
-                .takes_value(true))
-        .arg(Arg::with_name("metric")
-                .short("m")
-                .long("metric")
-                .help("Sets the metric (function) used for similarity testing. Equal checks that both strings are equal, and lev uses the Levenshtein distance")
-                .takes_value(true)
-                .required(true)
-                .possible_values(&["equal", "lev"]))
-        .arg(Arg::with_name("sensitivity")
-                .short("n")
-                .long("sensitivity")
-                .help("Sets the number of words required to form a unit of plagiarism checking")
-                .takes_value(true)
-                .required(true))
-        .arg(Arg::with_name("similarity")
-                .short("s")
-                .long("similarity")
-                .help("Sets the threshold value for plagiarism to be detected by a chosen metric")
-                .takes_value(true)
-                .required(true))
-        .arg(Arg::with_name("output-cli")
-                .long("cli")
-                .help("If the results should be printed to the command line"))
-        .arg(Arg::with_name("output-html")
-                .long("html")
-                .help("If the results should be printed to a HTML file"))
-        .arg(Arg::with_name("open-html")
-                .long("openhtml")
-                .help("If the HTML file should be opened automatically after writing")) ;
-
-    // Get options for algorithm
-    let matches = app.get_matches();
-    let n: usize = matches
-        .value_of("sensitivity")
-        .expect("Sensitivity value was not provided!")
-        .parse()
-        .expect("Sensitivity value provided was not a an integer!");
-    let s: usize = matches
-        .value_of("similarity")
-        .expect("Similarity value was not provided!")
-        .parse()
-        .expect("Similarity value provided was not a an integer!");
-    let metricarg: &str = matches.value_of("metric").expect("Metric not provided!");
-    let metric: Metric = match metricarg {
-        "equal" => Metric::Equal,
-        "lev" => Metric::Lev,
-        _ => panic!("Incorrect metric argument given!"),
+        .after_help("Running without a subcommand is the same as running the check subcommand. Flags can be turned off with --no-<flag> (e.g. --no-html), such as when a config file turns them on.")
+        .args(&args(CHECK_ARGS))
+        .subcommand(SubCommand::with_name("check")
+                .about("Checks untrusted submissions for plagiarism against each other and any trusted and archived texts")
+                .after_help(NEGATION_HELP)
+                .args(&args(CHECK_ARGS)))
+        .subcommand(SubCommand::with_name("index")
+                .about("Saves trusted and archived texts to an index file, so that later checks can use them (with --index) without reading them again")
+                .after_help(NEGATION_HELP)
+                .arg(Arg::with_name("output")
+                        .help("Sets the index file to save. Can be the same file as --index to add to it")
+                        .required(true))
+                .args(&args(INDEX_ARGS)))
+        .subcommand(SubCommand::with_name("report")
+                .about("Reports the results saved by a check with --incremental again, e.g. as HTML")
+                .after_help(NEGATION_HELP)
+                .arg(Arg::with_name("state")
+                        .help("Sets the incremental state file to read the results from")
+                        .required(true))
+                .args(&args(REPORT_ARGS)))
+        .subcommand(SubCommand::with_name("compare")
+                .about("Compares two files with each other and prints the matching text")
+                .after_help(NEGATION_HELP)
+                .arg(Arg::with_name("file1")
+                        .help("Sets the first file to compare")
+                        .required(true))
+                .arg(Arg::with_name("file2")
+                        .help("Sets the second file to compare")
+                        .required(true))
+                .arg(Arg::with_name("view")
+                        .long("view")
+                        .help("Sets whether the files are shown one after the other (unified) or next to each other (side-by-side)")
+                        .takes_value(true)
+                        .possible_values(&["unified", "side-by-side"])
+                        .default_value("unified"))
+                .arg(Arg::with_name("width")
+                        .long("width")
+                        .help("Sets the number of characters to wrap lines at. Defaults to the COLUMNS environment variable, or 100")
+                        .takes_value(true))
+                .args(&args(COMPARE_ARGS)))
+        .subcommand(SubCommand::with_name("query")
+                .about("Looks up runs and results stored in a results database by --results-db")
+                .setting(ClapSettings::SubcommandRequiredElseHelp)
+                .arg(Arg::with_name("database")
+                        .help("Sets the results database to read")
+                        .required(true))
+                .subcommand(SubCommand::with_name("runs")
+                        .about("Lists every stored run"))
+                .subcommand(SubCommand::with_name("top")
+                        .about("Lists the pairs with the highest plagiarism percentages in a run")
+                        .arg(Arg::with_name("run")
+                                .long("run")
+                                .help("Sets the run to list. Defaults to the latest run")
+                                .takes_value(true))
+                        .arg(Arg::with_name("limit")
+                                .long("limit")
+                                .help("Sets the number of pairs to list")
+                                .takes_value(true)
+                                .default_value("20")))
+                .subcommand(SubCommand::with_name("student")
+                        .about("Shows every result involving a student, with all of its matches")
+                        .arg(Arg::with_name("student")
+                                .help("Sets the student, by submission name or by student ID in the manifest")
+                                .required(true))
+                        .arg(Arg::with_name("run")
+                                .long("run")
+                                .help("Sets the run to search. Defaults to the latest run")
+                                .takes_value(true)))
+                .subcommand(SubCommand::with_name("diff")
+                        .about("Shows the results that were added, removed or changed between two runs")
+                        .arg(Arg::with_name("run1")
+                                .help("Sets the earlier run")
+                                .required(true))
+                        .arg(Arg::with_name("run2")
+                                .help("Sets the later run")
+                                .required(true))));
+
+    let matches = app.get_matches_safe().unwrap_or_else(|err| exit_with(err));
+    match matches.subcommand() {
+        ("check", Some(matches)) => CliCommand::Check(Box::new(get_check_settings(matches))),
+        ("index", Some(matches)) => {
+            let (config, _) = get_config(matches);
+            require(
+                config.trusted.is_some() || config.archive.is_some(),
+                "--trusted <trusted-directory> or --archive <archive-directory>",
+            );
+            require(config.sensitivity.is_some(), "--sensitivity <sensitivity>");
+            let mut appsettings = config.to_app_settings();
+            appsettings.save_index = matches.value_of("output").map(|x| x.to_string());
+            CliCommand::Index(Box::new(appsettings))
+        }
+        ("report", Some(matches)) => {
+            // The settings the results were checked with are saved in the state file
+            let (config, _) = get_config(matches);
+            require_with(
+                config.section.is_some(),
+                "--section <section>",
+                config.manifest.is_some(),
+                "--manifest <manifest>",
+            );
+            CliCommand::Report(
+                matches
+                    .value_of("state")
+                    .expect("State file not provided!")
+                    .to_string(),
+                Box::new(config.to_app_settings()),
+            )
+        }
+        ("compare", Some(matches)) => {
+            let (config, _) = get_config(matches);
+            require(config.metric.is_some(), "--metric <metric>");
+            require(config.sensitivity.is_some(), "--sensitivity <sensitivity>");
+            require(config.similarity.is_some(), "--similarity <similarity>");
+            check_comments_options(&config);
+            let view = match matches.value_of("view") {
+                Some("side-by-side") => CompareView::SideBySide,
+                _ => CompareView::Unified,
+            };
+            let width: usize = match matches.value_of("width") {
+                Some(x) => x.parse().expect("Width provided was not an integer!"),
+                None => env::var("COLUMNS")
+                    .ok()
+                    .and_then(|x| x.parse().ok())
+                    .unwrap_or(100),
+            };
+            CliCommand::Compare {
+                file1: matches
+                    .value_of("file1")
+                    .expect("First file not provided!")
+                    .to_string(),
+                file2: matches
+                    .value_of("file2")
+                    .expect("Second file not provided!")
+                    .to_string(),
+                view,
+                width,
+                appsettings: Box::new(config.to_app_settings()),
+            }
+        }
+        ("query", Some(matches)) => {
+            require_feature(cfg!(feature = "sqlite"), "The query subcommand", "sqlite");
+            get_query(matches)
+        }
+        _ => CliCommand::Check(Box::new(get_check_settings(&matches))),
+    }
+}
+
+/// Reads the settings given to the check subcommand (or no subcommand)
+fn get_check_settings(matches: &ArgMatches) -> AppSettings {
+    let (config, sources) = get_config(matches);
+    require(
+        config.untrusted.is_some(),
+        "--untrusted <untrusted-directory>",
+    );
+    require(config.metric.is_some(), "--metric <metric>");
+    require(config.sensitivity.is_some(), "--sensitivity <sensitivity>");
+    require(config.similarity.is_some(), "--similarity <similarity>");
+    require_with(
+        config.section.is_some(),
+        "--section <section>",
+        config.manifest.is_some(),
+        "--manifest <manifest>",
+    );
+    check_comments_options(&config);
+    if config.incremental.is_some() && config.boilerplate.is_some() {
+        exit_with(clap::Error::with_description(
+            "--incremental <incremental> can't be used with --boilerplate <boilerplate>, \
+             whether they are given on the command line or in a config file",
+            ErrorKind::ArgumentConflict,
+        ));
+    }
+    require_feature(
+        config.results_db.is_none() || cfg!(feature = "sqlite"),
+        "--results-db <file>",
+        "sqlite",
+    );
+    let mut appsettings = config.to_app_settings();
+    appsettings.effective_config = Some(config.describe(&sources));
+    appsettings
+}
+
+/// Reads the settings in the config files, overridden by the ones on the command line
+fn get_config(matches: &ArgMatches) -> (Config, Vec<String>) {
+    let (config, mut sources) =
+        load_config(matches.value_of("config"), matches.value_of("profile"));
+    sources.push("the command line".to_string());
+    (config.merge(get_config_from_args(matches)), sources)
+}
+
+/// Reads the settings given on the command line. Only options that were actually
+///     given are set, so that defaults don't override settings in config files.
+///     Not every subcommand takes every option.
+fn get_config_from_args(matches: &ArgMatches) -> Config {
+    let value = |name: &str| {
+        matches
+            .value_of(name)
+            .filter(|_| matches.occurrences_of(name) > 0)
+            .map(|x| x.to_string())
     };
+    let number = |name: &str, expect: &str| value(name).map(|x| x.parse().expect(expect));
+    // A flag is set if it or the option turning it off was given, whichever was last
+    let flag = |name: &str| {
+        let (_, negation) = NEGATABLE_FLAGS
+            .iter()
+            .find(|(flag, _)| *flag == name)
+            .expect("Flag can't be turned off!");
+        if matches.is_present(name) {
+            Some(true)
+        } else if matches.is_present(negation) {
+            Some(false)
+        } else {
+            None
+        }
+    };
+    Config {
+        untrusted: value("untrusted-directory"),
+        trusted: value("trusted-directory"),
+        archive: value("archive-directory"),
+        ignore: value("ignore-directory"),
+        index: value("index"),
+        save_index: value("save-index"),
+        incremental: value("incremental"),
+        results_db: value("results-db"),
+        metric: value("metric"),
+        sensitivity: number(
+            "sensitivity",
+            "Sensitivity value provided was not a an integer!",
+        ),
+        similarity: number(
+            "similarity",
+            "Similarity value provided was not a an integer!",
+        ),
+        ignore_metric: value("ignore-metric"),
+        ignore_similarity: number(
+            "ignore-similarity",
+            "Ignore similarity value provided was not a an integer!",
+        ),
+        exclude_stopwords: flag("exclude-stopwords"),
+        boilerplate: value("boilerplate").map(|x| {
+            x.parse()
+                .expect("Boilerplate frequency must be a number between 0 and 1!")
+        }),
+        dedup_sources: flag("dedup-sources"),
+        manifest: value("manifest"),
+        section: value("section"),
+        groups: value("groups"),
+        recursive: flag("recursive"),
+        submission_dirs: flag("submission-dirs"),
+        glob: matches
+            .values_of("glob")
+            .map(|globs| globs.map(String::from).collect()),
+        symlinks: value("symlinks"),
+        hidden: flag("hidden"),
+        language: value("language"),
+        cross_language: flag("cross-language"),
+        comments: flag("split-comments"),
+        comments_sensitivity: number(
+            "comments-sensitivity",
+            "Comments sensitivity value provided was not a an integer!",
+        ),
+        comments_similarity: number(
+            "comments-similarity",
+            "Comments similarity value provided was not a an integer!",
+        ),
+        cli: flag("output-cli"),
+        html: flag("output-html"),
+        openhtml: flag("open-html"),
+        quiet: flag("quiet"),
+        fail_threshold: value("fail-threshold").map(|x| {
+            x.parse()
+                .expect("Fail threshold must be a percentage between 0 and 100!")
+        }),
+        profiles: HashMap::new(),
+    }
+}
+
+/// Exits with an error like clap's if a required option was given neither on the
+///     command line nor in a config file
+fn require(given: bool, option: &str) {
+    if !given {
+        exit_with(clap::Error::with_description(
+            &format!(
+                "The following required argument was not provided on the command line \
+                 or in a config file:\n    {}",
+                option
+            ),
+            ErrorKind::MissingRequiredArgument,
+        ));
+    }
+}
+
+/// Exits with an error like clap's if an option was given without another option
+///     it needs, on the command line or in a config file
+fn require_with(given: bool, option: &str, needed_given: bool, needed: &str) {
+    if given && !needed_given {
+        exit_with(clap::Error::with_description(
+            &format!(
+                "{} can only be used with {}, which was not provided on the command line \
+                 or in a config file",
+                option, needed
+            ),
+            ErrorKind::MissingRequiredArgument,
+        ));
+    }
+}
+
+/// Exits with an error like clap's if comments are compared with settings of their
+///     own without being compared separately
+fn check_comments_options(config: &Config) {
+    let comments = config.comments == Some(true);
+    for (given, option) in [
+        (
+            config.comments_sensitivity.is_some(),
+            "--comments-sensitivity <comments-sensitivity>",
+        ),
+        (
+            config.comments_similarity.is_some(),
+            "--comments-similarity <comments-similarity>",
+        ),
+    ] {
+        require_with(given, option, comments, "--comments");
+    }
+}
+
+/// Exits with an error like clap's if an option needs a feature that was left out
+///     of this build
+fn require_feature(available: bool, option: &str, feature: &str) {
+    if !available {
+        exit_with(clap::Error::with_description(
+            &format!(
+                "{} can't be used because this build doesn't include the {} feature",
+                option, feature
+            ),
+            ErrorKind::InvalidValue,
+        ));
+    }
+}
+
+/// Exits because of a command line error, with the same exit code as other errors.
+///     Help and version requests exit successfully as usual.
+fn exit_with(err: clap::Error) -> ! {
+    if err.use_stderr() {
+        eprintln!("{}", err.message);
+        process::exit(EXIT_ERROR);
+    }
+    err.exit()
+}
+
+/// Reads the database and query given to the query subcommand
+fn get_query(matches: &ArgMatches) -> CliCommand {
+    let database = matches
+        .value_of("database")
+        .expect("Results database not provided!")
+        .to_string();
+    let parse_run = |x: &str| -> i64 { x.parse().expect("Run provided was not an integer!") };
+    let query = match matches.subcommand() {
+        ("runs", _) => Query::Runs,
+        ("top", Some(matches)) => Query::TopPairs {
+            run: matches.value_of("run").map(parse_run),
+            limit: matches
+                .value_of("limit")
+                .expect("Limit not provided!")
+                .parse()
+                .expect("Limit provided was not an integer!"),
+        },
+        ("student", Some(matches)) => Query::Student {
+            student: matches
+                .value_of("student")
+                .expect("Student not provided!")
+                .to_string(),
+            run: matches.value_of("run").map(parse_run),
+        },
+        ("diff", Some(matches)) => Query::Diff {
+            run1: parse_run(matches.value_of("run1").expect("First run not provided!")),
+            run2: parse_run(matches.value_of("run2").expect("Second run not provided!")),
+        },
+        _ => panic!("Incorrect query given!"),
+    };
+    CliCommand::Query(database, query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Get info from directories
-    let udir: &str = matches
-        .value_of("untrusted-directory")
-        .expect("Untrusted directory not provided!");
-    let tdir: Option<String> = matches.value_of("trusted-directory").map(|x| x.to_string());
-    let idir: Option<String> = matches.value_of("ignore-directory").map(|x| x.to_string());
-
-    // Get flag options
-    let output_cli = matches.is_present("output-cli");
-    let output_html = matches.is_present("output-html");
-    let open_html_after = matches.is_present("open-html");
-    AppSettings {
-        n,
-        s,
-        metric,
-        udir: udir.to_string(),
-        tdir,
-        idir,
-        output_cli,
-        output_html,
-        open_html_after,
+    #[test]
+    fn test_flags_turned_off() {
+        let config = |command_line: &[&str]| {
+            let matches = App::new("test")
+                .args(&args(CHECK_ARGS))
+                .get_matches_from(std::iter::once("test").chain(command_line.iter().copied()));
+            get_config_from_args(&matches)
+        };
+        assert_eq!(config(&["--html"]).html, Some(true));
+        assert_eq!(config(&["--no-html"]).html, Some(false));
+        assert_eq!(config(&[]).html, None);
+        // Whichever was given last wins
+        assert_eq!(config(&["--html", "--no-html"]).html, Some(false));
+        assert_eq!(
+            config(&["--no-comments", "--comments"]).comments,
+            Some(true)
+        );
     }
 }
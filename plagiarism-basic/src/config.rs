@@ -0,0 +1,285 @@
+use plagiarismbasic_lib::{AppSettings, GroupMode, Language, Metric, ScanOptions, SymlinkPolicy};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the project-local config file, which is looked for in the current directory
+pub const PROJECT_CONFIG: &str = "plagiarism-basic.toml";
+
+/// Settings from a config file, the command line, or both. Each key is named after
+///     the long command line option it stands for, e.g. `comments-sensitivity = 4`
+///     for --comments-sensitivity 4. Named profiles (e.g. `[profiles.essay]`) hold
+///     settings that are only used when the profile is picked with --profile.
+#[derive(Deserialize, Serialize, Default, Debug, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub untrusted: Option<String>,
+    pub trusted: Option<String>,
+    pub archive: Option<String>,
+    pub ignore: Option<String>,
+    pub index: Option<String>,
+    pub save_index: Option<String>,
+    pub incremental: Option<String>,
+    pub results_db: Option<String>,
+    pub metric: Option<String>,
+    pub sensitivity: Option<usize>,
+    pub similarity: Option<usize>,
+    pub ignore_metric: Option<String>,
+    pub ignore_similarity: Option<usize>,
+    pub exclude_stopwords: Option<bool>,
+    pub boilerplate: Option<f32>,
+    pub dedup_sources: Option<bool>,
+    pub manifest: Option<String>,
+    pub section: Option<String>,
+    pub groups: Option<String>,
+    pub recursive: Option<bool>,
+    pub submission_dirs: Option<bool>,
+    pub glob: Option<Vec<String>>,
+    pub symlinks: Option<String>,
+    pub hidden: Option<bool>,
+    pub language: Option<String>,
+    pub cross_language: Option<bool>,
+    pub comments: Option<bool>,
+    pub comments_sensitivity: Option<usize>,
+    pub comments_similarity: Option<usize>,
+    pub cli: Option<bool>,
+    pub html: Option<bool>,
+    pub openhtml: Option<bool>,
+    pub quiet: Option<bool>,
+    pub fail_threshold: Option<f32>,
+    #[serde(default, skip_serializing)]
+    pub profiles: HashMap<String, Config>,
+}
+
+impl Config {
+    /// Combines two configs, with the settings given in the second overriding the first
+    pub fn merge(self, over: Config) -> Config {
+        Config {
+            untrusted: over.untrusted.or(self.untrusted),
+            trusted: over.trusted.or(self.trusted),
+            archive: over.archive.or(self.archive),
+            ignore: over.ignore.or(self.ignore),
+            index: over.index.or(self.index),
+            save_index: over.save_index.or(self.save_index),
+            incremental: over.incremental.or(self.incremental),
+            results_db: over.results_db.or(self.results_db),
+            metric: over.metric.or(self.metric),
+            sensitivity: over.sensitivity.or(self.sensitivity),
+            similarity: over.similarity.or(self.similarity),
+            ignore_metric: over.ignore_metric.or(self.ignore_metric),
+            ignore_similarity: over.ignore_similarity.or(self.ignore_similarity),
+            exclude_stopwords: over.exclude_stopwords.or(self.exclude_stopwords),
+            boilerplate: over.boilerplate.or(self.boilerplate),
+            dedup_sources: over.dedup_sources.or(self.dedup_sources),
+            manifest: over.manifest.or(self.manifest),
+            section: over.section.or(self.section),
+            groups: over.groups.or(self.groups),
+            recursive: over.recursive.or(self.recursive),
+            submission_dirs: over.submission_dirs.or(self.submission_dirs),
+            glob: over.glob.or(self.glob),
+            symlinks: over.symlinks.or(self.symlinks),
+            hidden: over.hidden.or(self.hidden),
+            language: over.language.or(self.language),
+            cross_language: over.cross_language.or(self.cross_language),
+            comments: over.comments.or(self.comments),
+            comments_sensitivity: over.comments_sensitivity.or(self.comments_sensitivity),
+            comments_similarity: over.comments_similarity.or(self.comments_similarity),
+            cli: over.cli.or(self.cli),
+            html: over.html.or(self.html),
+            openhtml: over.openhtml.or(self.openhtml),
+            quiet: over.quiet.or(self.quiet),
+            fail_threshold: over.fail_threshold.or(self.fail_threshold),
+            profiles: HashMap::new(),
+        }
+    }
+
+    /// Converts the settings to the ones used by the checks. Settings that
+    ///     aren't given get their default values.
+    ///     Panics if a setting has a value that isn't allowed.
+    pub fn to_app_settings(&self) -> AppSettings {
+        let n = self.sensitivity.unwrap_or_default();
+        let s = self.similarity.unwrap_or_default();
+        let metric = match self.metric.as_deref() {
+            Some("equal") | None => Metric::Equal,
+            Some("lev") => Metric::Lev,
+            Some(_) => panic!("Incorrect metric argument given!"),
+        };
+        let ignore_metric = match self.ignore_metric.as_deref() {
+            Some("equal") => Metric::Equal,
+            Some("lev") => Metric::Lev,
+            Some(_) => panic!("Incorrect ignore metric argument given!"),
+            // Fuzzy matching against every ignored fragment is slow, so only if asked for
+            None => Metric::Equal,
+        };
+        let language: Option<Language> = self
+            .language
+            .as_ref()
+            .map(|x| Language::from_name(x).expect("Incorrect language argument given!"));
+        if let Some(threshold) = self.fail_threshold {
+            if !(0.0..=100.0).contains(&threshold) {
+                panic!("Fail threshold must be a percentage between 0 and 100!");
+            }
+        }
+        if let Some(frequency) = self.boilerplate {
+            if !(0.0..=1.0).contains(&frequency) {
+                panic!("Boilerplate frequency must be a number between 0 and 1!");
+            }
+        }
+        let group_mode = match self.groups.as_deref() {
+            Some("skip") | None => GroupMode::Skip,
+            Some("label") => GroupMode::Label,
+            Some("merge") => GroupMode::Merge,
+            Some(_) => panic!("Incorrect groups argument given!"),
+        };
+        let symlinks = match self.symlinks.as_deref() {
+            Some("follow") | None => SymlinkPolicy::Follow,
+            Some("skip") => SymlinkPolicy::Skip,
+            Some(_) => panic!("Incorrect symlinks argument given!"),
+        };
+        let flag = |value: Option<bool>| value.unwrap_or(false);
+        AppSettings {
+            n,
+            s,
+            metric,
+            udir: self.untrusted.clone().unwrap_or_default(),
+            tdir: self.trusted.clone(),
+            idir: self.ignore.clone(),
+            adir: self.archive.clone(),
+            index: self.index.clone(),
+            save_index: self.save_index.clone(),
+            incremental: self.incremental.clone(),
+            results_db: self.results_db.clone(),
+            ignore_metric,
+            ignore_s: self.ignore_similarity.unwrap_or(s),
+            exclude_stopwords: flag(self.exclude_stopwords),
+            boilerplate_frequency: self.boilerplate,
+            manifest: self.manifest.clone(),
+            section: self.section.clone(),
+            group_mode,
+            scan_options: ScanOptions {
+                recursive: flag(self.recursive),
+                globs: self.glob.clone().unwrap_or_default(),
+                symlinks,
+                include_hidden: flag(self.hidden),
+            },
+            submission_dirs: flag(self.submission_dirs),
+            language,
+            cross_language: flag(self.cross_language),
+            split_comments: flag(self.comments),
+            dedup_sources: flag(self.dedup_sources),
+            comments_n: self.comments_sensitivity.unwrap_or(n),
+            comments_s: self.comments_similarity.unwrap_or(s),
+            output_cli: flag(self.cli),
+            output_html: flag(self.html),
+            open_html_after: flag(self.openhtml),
+            quiet: flag(self.quiet),
+            fail_threshold: self.fail_threshold.unwrap_or(0.0),
+            effective_config: None,
+        }
+    }
+
+    /// Writes the settings out as a config file, noting where they came from
+    pub fn describe(&self, sources: &[String]) -> String {
+        let settings = toml::to_string(self).expect("Settings could not be converted to TOML!");
+        format!(
+            "# From {}. Settings that aren't listed have their default values\n{}",
+            sources.join(", then "),
+            settings
+        )
+    }
+}
+
+/// User-level config file, e.g. ~/.config/plagiarism-basic/config.toml on Linux
+fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("plagiarism-basic").join("config.toml"))
+}
+
+/// Reads a config file. Panics if it cannot be read or isn't a valid config.
+fn read_config(path: &Path) -> Config {
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("Cannot read config file {}: {}", path.display(), err));
+    toml::from_str(&text)
+        .unwrap_or_else(|err| panic!("Config file {} is not valid: {}", path.display(), err))
+}
+
+/// Loads the user-level config file and then the project-local one (or the given
+///     config file instead), each overriding the one before, then applies the given
+///     profile. Returns the settings and a description of where they came from.
+///     Panics if the profile isn't defined in any of the config files.
+pub fn load_config(config_file: Option<&str>, profile: Option<&str>) -> (Config, Vec<String>) {
+    let mut paths: Vec<PathBuf> = user_config_path()
+        .filter(|path| path.is_file())
+        .into_iter()
+        .collect();
+    match config_file {
+        Some(path) => paths.push(PathBuf::from(path)),
+        None if Path::new(PROJECT_CONFIG).is_file() => paths.push(PathBuf::from(PROJECT_CONFIG)),
+        None => (),
+    }
+    let mut sources: Vec<String> = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    let config = combine(paths.iter().map(|path| read_config(path)), profile);
+    if let Some(profile) = profile {
+        sources.push(format!("profile {}", profile));
+    }
+    (config, sources)
+}
+
+/// Combines configs, each overriding the ones before it, then applies a profile
+///     defined in any of them. A profile defined in more than one config is
+///     combined in the same order.
+fn combine(configs: impl IntoIterator<Item = Config>, profile: Option<&str>) -> Config {
+    let mut combined = Config::default();
+    let mut profile_config: Option<Config> = None;
+    for mut config in configs {
+        if let Some(settings) = profile.and_then(|profile| config.profiles.remove(profile)) {
+            profile_config = Some(profile_config.unwrap_or_default().merge(settings));
+        }
+        combined = combined.merge(config);
+    }
+    match (profile, profile_config) {
+        (Some(profile), None) => panic!("Profile {} is not defined in any config file", profile),
+        (_, profile_config) => combined.merge(profile_config.unwrap_or_default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine_configs_and_profiles() {
+        let user: Config = toml::from_str(
+            "metric = \"equal\"\nsensitivity = 10\nsimilarity = 0\n\
+             [profiles.strict]\nsensitivity = 5\n",
+        )
+        .unwrap();
+        let project: Config = toml::from_str(
+            "untrusted = \"submissions\"\nsensitivity = 8\ncli = true\n\
+             [profiles.strict]\nmetric = \"lev\"\nsimilarity = 1\n",
+        )
+        .unwrap();
+        let config = combine(vec![user, project], Some("strict"));
+        let appsettings = config.to_app_settings();
+        assert_eq!(appsettings.udir, "submissions");
+        assert_eq!((appsettings.n, appsettings.s), (5, 1));
+        assert_eq!(appsettings.metric, Metric::Lev);
+        // Ignored text is only matched fuzzily if asked for
+        assert_eq!(appsettings.ignore_metric, Metric::Equal);
+        assert!(appsettings.output_cli && !appsettings.output_html);
+
+        // The command line overrides everything
+        let command_line = Config {
+            sensitivity: Some(3),
+            ..Config::default()
+        };
+        assert_eq!(config.merge(command_line).to_app_settings().n, 3);
+
+        let result = std::panic::catch_unwind(|| combine(vec![Config::default()], Some("essay")));
+        assert!(result.is_err());
+        assert!(toml::from_str::<Config>("sensitivty = 3").is_err());
+    }
+}
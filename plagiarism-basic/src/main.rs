@@ -1,9 +1,53 @@
 mod cli_input;
-use plagiarismbasic_lib::{run_plagiarism_checks, AppSettings};
+mod config;
+use plagiarismbasic_lib::{
+    build_index, compare_files, report_saved_results, run_plagiarism_checks, run_query,
+};
+use std::panic;
+use std::process;
+
+use cli_input::{get_cli_input, CliCommand};
+
+/// Exit code when nothing was found
+const EXIT_NO_FINDINGS: i32 = 0;
+/// Exit code when there are findings (at or above --fail-threshold)
+const EXIT_FINDINGS: i32 = 1;
+/// Exit code when the run failed, e.g. because of invalid options or unreadable files
+pub const EXIT_ERROR: i32 = 2;
 
-use cli_input::get_cli_input;
 fn main() {
+    // Errors panic with a message, which is printed as usual before exiting
+    let exit_code = panic::catch_unwind(run).unwrap_or(EXIT_ERROR);
+    process::exit(exit_code);
+}
+
+/// Runs the command given on the CLI and returns the exit code
+fn run() -> i32 {
     // Read settings for algorithm from cli
-    let appsettings: AppSettings = get_cli_input();
-    run_plagiarism_checks(&appsettings);
+    let found = match get_cli_input() {
+        CliCommand::Check(appsettings) => run_plagiarism_checks(&appsettings).total() > 0,
+        CliCommand::Index(appsettings) => {
+            build_index(&appsettings);
+            false
+        }
+        CliCommand::Report(state, appsettings) => {
+            report_saved_results(&state, &appsettings).total() > 0
+        }
+        CliCommand::Compare {
+            file1,
+            file2,
+            view,
+            width,
+            appsettings,
+        } => compare_files(&file1, &file2, &appsettings, view, width),
+        CliCommand::Query(database, query) => {
+            run_query(&database, &query);
+            false
+        }
+    };
+    if found {
+        EXIT_FINDINGS
+    } else {
+        EXIT_NO_FINDINGS
+    }
 }
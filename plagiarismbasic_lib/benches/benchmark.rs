@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use plagiarismbasic_lib::{run_plagiarism_checks, AppSettings, Metric};
+use plagiarismbasic_lib::{run_plagiarism_checks, AppSettings, GroupMode, Metric, ScanOptions};
 use std::time::Duration;
 
 pub fn criterion_benchmark(c: &mut Criterion) {
@@ -15,9 +15,32 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         tdir: Some("testfiles/cs-corpus/t".to_string()),
         udir: "testfiles/cs-corpus/ut".to_string(),
         idir: None,
+        adir: None,
+        index: None,
+        save_index: None,
+        incremental: None,
+        results_db: None,
+        ignore_metric: Metric::Equal,
+        ignore_s: 0,
+        boilerplate_frequency: None,
+        exclude_stopwords: false,
+        manifest: None,
+        section: None,
+        group_mode: GroupMode::Skip,
+        scan_options: ScanOptions::default(),
+        submission_dirs: false,
+        language: None,
+        cross_language: false,
+        split_comments: false,
+        dedup_sources: false,
+        comments_n: 10,
+        comments_s: 0,
         output_cli: false,
         output_html: true,
         open_html_after: false,
+        quiet: false,
+        fail_threshold: 0.0,
+        effective_config: None,
     };
     // Long running function
     group.bench_function("run_plag nocli + html + no-open-html", |b| {
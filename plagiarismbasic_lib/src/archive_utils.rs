@@ -0,0 +1,251 @@
+use flate2::read::GzDecoder;
+use std::io::{Cursor, Read};
+
+/// Archive extensions we can read, longest first so that .tar.gz wins over .gz
+const ARCHIVE_EXTENSIONS: [&str; 4] = [".tar.gz", ".tgz", ".tar", ".zip"];
+
+/// Deepest nesting of archives inside archives that is expanded. Archives nested
+///     deeper are skipped.
+const MAX_NESTING_DEPTH: usize = 3;
+
+/// Most bytes expanded from one archive (including the archives inside it),
+///     so that e.g. a zip bomb can't use up all memory
+const MAX_EXPANDED_BYTES: u64 = 1 << 30;
+
+/// A file in an archive as (path in archive, contents), or the reason it
+///     couldn't be read, e.g. for a corrupt archive inside the archive
+pub type ArchiveEntry = (String, Result<Vec<u8>, String>);
+
+/// Checks if a file name looks like an archive we can read
+pub fn is_archive(name: &str) -> bool {
+    archive_extension(name).is_some()
+}
+
+/// Strips the archive extension from a file name, e.g. alice.tar.gz -> alice
+pub fn strip_archive_extension(name: &str) -> &str {
+    match archive_extension(name) {
+        Some(extension) => &name[..name.len() - extension.len()],
+        None => name,
+    }
+}
+
+fn archive_extension(name: &str) -> Option<&'static str> {
+    let lowercase = name.to_lowercase();
+    ARCHIVE_EXTENSIONS
+        .iter()
+        .find(|extension| lowercase.ends_with(*extension))
+        .copied()
+}
+
+/// Reads every file in an archive into memory.
+///     Archives inside the archive are expanded too, with their entries placed
+///     under a folder named after the inner archive (alice.zip -> alice/...).
+///     Inner archives that can't be read, or are nested too deep, are returned
+///     as entries with the reason instead. Entries are sorted by path.
+///     Returns the reason if the archive itself can't be read (e.g. it is
+///     corrupt or truncated) or expands to too many bytes.
+pub fn read_archive_entries(name: &str, bytes: &[u8]) -> Result<Vec<ArchiveEntry>, String> {
+    let mut budget = MAX_EXPANDED_BYTES;
+    let mut entries = expand_archive(name, bytes, 1, &mut budget)?;
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(entries)
+}
+
+/// Reads the files in an archive nested depth deep (1 for the outermost),
+///     taking the bytes read out of the budget
+fn expand_archive(
+    name: &str,
+    bytes: &[u8],
+    depth: usize,
+    budget: &mut u64,
+) -> Result<Vec<ArchiveEntry>, String> {
+    let lowercase = name.to_lowercase();
+    let entries = if lowercase.ends_with(".zip") {
+        read_zip_entries(bytes, budget)?
+    } else if lowercase.ends_with(".tar") {
+        read_tar_entries(bytes, budget)?
+    } else {
+        let tar_bytes = read_limited(GzDecoder::new(bytes), budget)
+            .map_err(|reason| format!("cannot decompress archive: {}", reason))?;
+        read_tar_entries(&tar_bytes, budget)?
+    };
+
+    let mut expanded: Vec<ArchiveEntry> = Vec::new();
+    for (path, contents) in entries {
+        if !is_archive(&path) {
+            expanded.push((path, Ok(contents)));
+        } else if depth >= MAX_NESTING_DEPTH {
+            let reason = format!("archive nested more than {} deep", MAX_NESTING_DEPTH);
+            expanded.push((path, Err(reason)));
+        } else {
+            match expand_archive(&path, &contents, depth + 1, budget) {
+                Ok(inner_entries) => {
+                    let folder = strip_archive_extension(&path).to_string();
+                    for (inner_path, inner_contents) in inner_entries {
+                        expanded.push((format!("{}/{}", folder, inner_path), inner_contents));
+                    }
+                }
+                Err(reason) => expanded.push((path, Err(reason))),
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+/// Reads everything from a reader, failing if it is more than the bytes left
+///     in the budget
+fn read_limited(reader: impl Read, budget: &mut u64) -> Result<Vec<u8>, String> {
+    let mut contents: Vec<u8> = Vec::new();
+    reader
+        .take(*budget + 1)
+        .read_to_end(&mut contents)
+        .map_err(|err| err.to_string())?;
+    if contents.len() as u64 > *budget {
+        return Err(format!(
+            "archive expands to more than {} MB",
+            MAX_EXPANDED_BYTES >> 20
+        ));
+    }
+    *budget -= contents.len() as u64;
+    Ok(contents)
+}
+
+fn read_zip_entries(bytes: &[u8], budget: &mut u64) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|err| format!("cannot open zip archive: {}", err))?;
+    let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index(i)
+            .map_err(|err| format!("cannot read entry {} of zip archive: {}", i, err))?;
+        if file.is_dir() {
+            continue;
+        }
+        // Skip entries with unsafe paths (e.g. containing ..)
+        let path = match file.enclosed_name() {
+            Some(path) => path.to_string_lossy().to_string(),
+            None => continue,
+        };
+        let contents = read_limited(file, budget)
+            .map_err(|reason| format!("cannot read {}: {}", path, reason))?;
+        entries.push((path, contents));
+    }
+    Ok(entries)
+}
+
+fn read_tar_entries(bytes: &[u8], budget: &mut u64) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut archive = tar::Archive::new(bytes);
+    let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+    for entry in archive
+        .entries()
+        .map_err(|err| format!("cannot open tar archive: {}", err))?
+    {
+        let entry = entry.map_err(|err| format!("cannot read entry of tar archive: {}", err))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry
+            .path()
+            .map_err(|err| format!("invalid entry path in tar archive: {}", err))?
+            .to_string_lossy()
+            .trim_start_matches("./")
+            .to_string();
+        let contents = read_limited(entry, budget)
+            .map_err(|reason| format!("cannot read {}: {}", path, reason))?;
+        entries.push((path, contents));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn make_tar_gz(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn make_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, contents) in files {
+            writer
+                .start_file(*path, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_strip_archive_extension() {
+        assert_eq!(strip_archive_extension("alice.TAR.GZ"), "alice");
+        assert_eq!(strip_archive_extension("bob.zip"), "bob");
+        assert_eq!(strip_archive_extension("essay.txt"), "essay.txt");
+    }
+
+    #[test]
+    fn test_nested_archives() {
+        let bob = make_tar_gz(&[("./essay.txt", "bob's essay")]);
+        let outer = make_zip(&[("alice/essay.txt", b"alice's essay"), ("bob.tar.gz", &bob)]);
+        let entries: Vec<(String, String)> = read_archive_entries("subs.zip", &outer)
+            .unwrap()
+            .into_iter()
+            .map(|(path, contents)| (path, String::from_utf8(contents.unwrap()).unwrap()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("alice/essay.txt".to_string(), "alice's essay".to_string()),
+                ("bob/essay.txt".to_string(), "bob's essay".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreadable_archives() {
+        // A truncated archive is reported instead of panicking
+        let bob = make_tar_gz(&[("essay.txt", "bob's essay")]);
+        assert!(read_archive_entries("bob.tar.gz", &bob[..bob.len() / 2]).is_err());
+        assert!(read_archive_entries("subs.zip", b"PK\x03\x04 not a zip").is_err());
+
+        // A corrupt archive inside an archive only skips that archive
+        let outer = make_zip(&[("alice.txt", b"alice's essay"), ("bob.zip", b"corrupt")]);
+        let entries = read_archive_entries("subs.zip", &outer).unwrap();
+        assert_eq!(
+            entries[0],
+            ("alice.txt".to_string(), Ok(b"alice's essay".to_vec()))
+        );
+        assert_eq!(entries[1].0, "bob.zip");
+        assert!(entries[1].1.is_err());
+
+        // Archives nested too deep are skipped
+        let mut nested = make_zip(&[("essay.txt", b"deep")]);
+        for _ in 0..MAX_NESTING_DEPTH {
+            nested = make_zip(&[("inner.zip", &nested)]);
+        }
+        let entries = read_archive_entries("outer.zip", &nested).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].1.is_err());
+    }
+
+    #[test]
+    fn test_read_limited() {
+        let mut budget = 10;
+        assert_eq!(read_limited(&b"hello"[..], &mut budget).unwrap(), b"hello");
+        assert_eq!(budget, 5);
+        assert!(read_limited(&b"too long"[..], &mut budget).is_err());
+    }
+}
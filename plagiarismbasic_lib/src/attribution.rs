@@ -0,0 +1,180 @@
+use crate::plagiarism_database::{Channel, CleanText, Coverage, PlagiarismResult, TextOwnerID};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// One trusted source's share of an untrusted text
+#[derive(Debug)]
+pub struct AttributedSource {
+    pub owner: TextOwnerID,
+    /// Percentage of all words in the untrusted text attributed to this source
+    pub percent: f32,
+}
+
+/// Which trusted sources cover which passages of one untrusted text
+#[derive(Debug)]
+pub struct Attribution {
+    pub owner: TextOwnerID,
+    pub channel: Channel,
+    /// Sources that cover the text, most of the text first
+    pub sources: Vec<AttributedSource>,
+    /// For each word of the text, the index in sources of the source it is attributed to
+    pub word_sources: Vec<Option<usize>>,
+    /// How much of the text is attributed to any trusted source
+    pub total: Coverage,
+}
+
+/// Merges every trusted result for each untrusted text into one attribution of
+///     the text's words to sources. A word matched by several sources is attributed
+///     to the best source, as for attribute_to_best_sources (by coverage of the text,
+///     then date of the source, then ID).
+///     Returns the attributions with the most attributed text first.
+pub fn attribute_sources(
+    trusted_results: &[PlagiarismResult],
+    texts: &HashMap<Channel, HashMap<TextOwnerID, CleanText>>,
+    source_dates: &HashMap<TextOwnerID, String>,
+    exclude_stopwords: bool,
+) -> Vec<Attribution> {
+    let mut text_results: HashMap<(Channel, &str), Vec<&PlagiarismResult>> = HashMap::new();
+    for result in trusted_results {
+        text_results
+            .entry((result.channel, &result.owner_id2))
+            .or_default()
+            .push(result);
+    }
+
+    let mut attributions: Vec<Attribution> = Vec::new();
+    for ((channel, owner), mut results) in text_results {
+        let text = texts
+            .get(&channel)
+            .and_then(|channel_texts| channel_texts.get(owner))
+            .unwrap_or_else(|| panic!("Could not find text for owner {} in text map", owner));
+        results.sort_by(|a, b| a.cmp_sources(b, source_dates));
+
+        let mut word_sources: Vec<Option<usize>> = vec![None; text.words.len()];
+        for (source_index, result) in results.iter().enumerate() {
+            for (_, locations) in &result.matching_fragments_locations {
+                for (start, end) in locations {
+                    for word_source in &mut word_sources[*start..=*end] {
+                        word_source.get_or_insert(source_index);
+                    }
+                }
+            }
+        }
+        let sources: Vec<AttributedSource> = results
+            .iter()
+            .enumerate()
+            .map(|(source_index, result)| {
+                let words = word_sources
+                    .iter()
+                    .filter(|word_source| **word_source == Some(source_index))
+                    .count();
+                AttributedSource {
+                    owner: result.owner_id1.clone(),
+                    percent: (words as f32) / (text.words.len() as f32) * 100.0,
+                }
+            })
+            .collect();
+        let total = text.coverage(
+            results
+                .iter()
+                .flat_map(|result| &result.matching_fragments_locations)
+                .flat_map(|(_, locations)| locations),
+            exclude_stopwords,
+        );
+        attributions.push(Attribution {
+            owner: owner.to_string(),
+            channel,
+            sources,
+            word_sources,
+            total,
+        });
+    }
+    attributions.sort_by(|a, b| {
+        b.total
+            .raw_percent
+            .partial_cmp(&a.total.raw_percent)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.owner.cmp(&b.owner))
+    });
+    attributions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_utils::Language;
+    use crate::plagiarism_database::PlagiarismDatabase;
+    use crate::Metric;
+
+    #[test]
+    fn test_attribute_sources() {
+        let mut db = PlagiarismDatabase::new(3, 0, Metric::Equal, Vec::new());
+        for (owner, text) in [
+            ("wikipedia", "rust is a systems programming language"),
+            ("blog", "it was created by graydon hoare"),
+        ] {
+            let files = vec![(owner.to_string(), text.to_string())];
+            db.add_trusted_submission(owner, &files, Language::Text);
+        }
+        let files = vec![(
+            "alice".to_string(),
+            "rust is a systems programming language that was created by graydon hoare at mozilla"
+                .to_string(),
+        )];
+        db.add_untrusted_submission("alice", &files, Language::Text);
+        let mut texts = HashMap::new();
+        texts.insert(Channel::Content, db.get_all_cleantext());
+
+        let attributions = attribute_sources(
+            &db.check_trusted_plagiarism(),
+            &texts,
+            &HashMap::new(),
+            false,
+        );
+        assert_eq!(attributions.len(), 1);
+        let alice = &attributions[0];
+        let sources: Vec<&str> = alice.sources.iter().map(|s| s.owner.as_str()).collect();
+        assert_eq!(sources, vec!["wikipedia", "blog"]);
+        assert_eq!(
+            alice.word_sources,
+            [
+                vec![Some(0); 6],
+                vec![None],
+                vec![Some(1); 5],
+                vec![None; 2]
+            ]
+            .concat()
+        );
+        assert!((alice.total.raw_percent - 100.0 * 11.0 / 14.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_attribute_sources_by_date() {
+        let mut db = PlagiarismDatabase::new(3, 0, Metric::Equal, Vec::new());
+        let text = "rust is a systems programming language";
+        for owner in ["alpha", "zeta", "alice"] {
+            let files = vec![(owner.to_string(), text.to_string())];
+            if owner == "alice" {
+                db.add_untrusted_submission(owner, &files, Language::Text);
+            } else {
+                db.add_trusted_submission(owner, &files, Language::Text);
+            }
+        }
+        let mut texts = HashMap::new();
+        texts.insert(Channel::Content, db.get_all_cleantext());
+        let results = db.check_trusted_plagiarism();
+
+        // Both sources cover all of alice's text, so the first by ID wins...
+        let attributions = attribute_sources(&results, &texts, &HashMap::new(), false);
+        assert_eq!(attributions[0].sources[0].owner, "alpha");
+        // ...unless one of them is dated, or dated earlier
+        let mut source_dates = HashMap::new();
+        source_dates.insert("zeta".to_string(), "2001-01-01".to_string());
+        let attributions = attribute_sources(&results, &texts, &source_dates, false);
+        assert_eq!(attributions[0].sources[0].owner, "zeta");
+        source_dates.insert("alpha".to_string(), "2020-01-01".to_string());
+        let attributions = attribute_sources(&results, &texts, &source_dates, false);
+        assert_eq!(attributions[0].sources[0].owner, "zeta");
+        assert_eq!(attributions[0].sources[1].percent, 0.0);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::file_utils::Language;
+
+/// Comment and string literal syntax for a family of languages
+struct CommentSyntax {
+    line_comment: &'static str,
+    block_comment: Option<(&'static str, &'static str)>,
+    /// String delimiters, longest first so that e.g. triple quotes win
+    string_delimiters: &'static [&'static str],
+}
+
+const C_LIKE: CommentSyntax = CommentSyntax {
+    line_comment: "//",
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &["\"", "'"],
+};
+
+// Single quotes are lifetimes as well as char literals in Rust, so only
+// double-quoted strings are treated as literals
+const RUST: CommentSyntax = CommentSyntax {
+    line_comment: "//",
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &["\""],
+};
+
+const JAVASCRIPT: CommentSyntax = CommentSyntax {
+    line_comment: "//",
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &["\"", "'", "`"],
+};
+
+const PYTHON: CommentSyntax = CommentSyntax {
+    line_comment: "#",
+    block_comment: None,
+    string_delimiters: &["\"\"\"", "'''", "\"", "'"],
+};
+
+fn comment_syntax(language: Language) -> Option<CommentSyntax> {
+    match language {
+        Language::Text => None,
+        Language::C | Language::Cpp | Language::Java => Some(C_LIKE),
+        Language::Rust => Some(RUST),
+        Language::JavaScript => Some(JAVASCRIPT),
+        Language::Python => Some(PYTHON),
+    }
+}
+
+/// Splits source code into (code, comments) where comments contains every
+///     comment and string literal (docstrings included) and code contains
+///     everything else. Each extracted piece is separated by a newline so
+///     words from neighbouring pieces are not joined together.
+///     Returns None for natural language text, which has no comments.
+pub fn split_comments(text: &str, language: Language) -> Option<(String, String)> {
+    let syntax = comment_syntax(language)?;
+    let mut code = String::new();
+    let mut comments = String::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with(syntax.line_comment) {
+            let end = rest.find('\n').unwrap_or(rest.len());
+            comments.push_str(&rest[syntax.line_comment.len()..end]);
+            comments.push('\n');
+            rest = &rest[end..];
+            code.push(' ');
+        } else if let Some((open, close)) = syntax
+            .block_comment
+            .filter(|(open, _)| rest.starts_with(open))
+        {
+            let body = &rest[open.len()..];
+            let end = body.find(close).unwrap_or(body.len());
+            comments.push_str(&body[..end]);
+            comments.push('\n');
+            rest = &body[(end + close.len()).min(body.len())..];
+            code.push(' ');
+        } else if let Some(delimiter) = syntax
+            .string_delimiters
+            .iter()
+            .find(|delimiter| rest.starts_with(**delimiter))
+        {
+            let body = &rest[delimiter.len()..];
+            let end = find_string_end(body, delimiter);
+            comments.push_str(&body[..end]);
+            comments.push('\n');
+            rest = &body[(end + delimiter.len()).min(body.len())..];
+            code.push(' ');
+        } else {
+            code.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    Some((code, comments))
+}
+
+/// Finds the index of the closing delimiter of a string, skipping escaped characters.
+///     Returns the length of the string if it is never closed.
+fn find_string_end(body: &str, delimiter: &str) -> usize {
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if body[i..].starts_with(delimiter) {
+            return i;
+        } else if c == '\n' && delimiter.len() == 1 && delimiter != "`" {
+            // Unterminated single-line string, stop here rather than
+            // swallowing the rest of the file
+            return i;
+        }
+    }
+    body.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_c_like() {
+        let (code, comments) = split_comments(
+            "int x = 1; // the answer\n/* block\ncomment */ printf(\"hi \\\" there\");",
+            Language::C,
+        )
+        .unwrap();
+        assert_eq!(comments, " the answer\n block\ncomment \nhi \\\" there\n");
+        assert_eq!(code, "int x = 1;  \n  printf( );");
+    }
+
+    #[test]
+    fn test_split_python_docstring() {
+        let (code, comments) = split_comments(
+            "def f():\n    \"\"\"Adds one.\"\"\"\n    return x + 1  # increment\n",
+            Language::Python,
+        )
+        .unwrap();
+        assert_eq!(comments, "Adds one.\n increment\n");
+        assert_eq!(code, "def f():\n     \n    return x + 1   \n");
+    }
+
+    #[test]
+    fn test_split_keeps_code_around_comments_apart() {
+        let (code, comments) = split_comments("a/*x*/b", Language::C).unwrap();
+        assert_eq!(comments, "x\n");
+        assert_eq!(code, "a b");
+    }
+
+    #[test]
+    fn test_split_text() {
+        assert_eq!(split_comments("Hello // world", Language::Text), None);
+    }
+}
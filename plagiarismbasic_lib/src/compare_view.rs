@@ -0,0 +1,261 @@
+use crate::plagiarism_database::{CleanText, FragmentLocation, PlagiarismResult, TextOwnerID};
+use colored::{Color, Colorize};
+use std::collections::HashMap;
+
+/// How the two texts of a comparison are shown in the terminal
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CompareView {
+    /// One text after the other
+    Unified,
+    /// Both texts next to each other in two columns
+    SideBySide,
+}
+
+/// Colours that matching passages cycle through, so that each passage has the
+///     same colour in both texts
+const PASSAGE_COLORS: [Color; 6] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+];
+
+/// Separates the columns of the side-by-side view
+const COLUMN_SEPARATOR: &str = " | ";
+
+/// Prints the result of comparing two texts: a similarity score, then both texts
+///     with each matching passage coloured the same in both. The text of the
+///     first of the owners given is shown first, whichever owner of the result it
+///     is. Each text is named by the label given for its owner.
+pub fn print_comparison(
+    result: &PlagiarismResult,
+    texts: &HashMap<TextOwnerID, CleanText>,
+    owners: [&str; 2],
+    labels: [&str; 2],
+    view: CompareView,
+    width: usize,
+) {
+    let swap = result.owner_id1 != owners[0];
+    let order = |pair: (f32, f32)| if swap { (pair.1, pair.0) } else { pair };
+    let (owner1, owner2) = if swap {
+        (&result.owner_id2, &result.owner_id1)
+    } else {
+        (&result.owner_id1, &result.owner_id2)
+    };
+    let (text1, text2) = (&texts[owner1], &texts[owner2]);
+    let (label1, label2) = (labels[0], labels[1]);
+    let (percent1, percent2) = order((result.coverage1.raw_percent, result.coverage2.raw_percent));
+    let locations: Vec<(Vec<FragmentLocation>, Vec<FragmentLocation>)> = result
+        .matching_fragments_locations
+        .iter()
+        .map(|(locations1, locations2)| match swap {
+            true => (locations2.clone(), locations1.clone()),
+            false => (locations1.clone(), locations2.clone()),
+        })
+        .collect();
+
+    println!(
+        "\nSimilarity: {:.0}% ({} vs {}, {})",
+        similarity((percent1, text1.words.len()), (percent2, text2.words.len())),
+        label1,
+        label2,
+        result.channel.name()
+    );
+    println!(
+        "Matching text covers {:.0}% vs {:.0}% of all words, in {} matching fragments\n",
+        percent1,
+        percent2,
+        result.matching_fragments.len()
+    );
+    let (colors1, colors2) = passage_colors(text1.words.len(), text2.words.len(), &locations);
+    match view {
+        CompareView::Unified => {
+            for (label, text, colors) in [(label1, text1, &colors1), (label2, text2, &colors2)] {
+                println!("{}", label.bold());
+                for line in wrap(&text.words, width) {
+                    println!("{}", render_line(&text.words, colors, line).0);
+                }
+                println!();
+            }
+        }
+        CompareView::SideBySide => {
+            let column_width = width.saturating_sub(COLUMN_SEPARATOR.len()).max(2) / 2;
+            let lines1 = wrap(&text1.words, column_width);
+            let lines2 = wrap(&text2.words, column_width);
+            let pad = |text: String, len: usize| {
+                format!("{}{}", text, " ".repeat(column_width.saturating_sub(len)))
+            };
+            println!(
+                "{}{}{}",
+                pad(label1.bold().to_string(), label1.chars().count()),
+                COLUMN_SEPARATOR,
+                label2.bold()
+            );
+            for i in 0..lines1.len().max(lines2.len()) {
+                let (line1, len1) = lines1
+                    .get(i)
+                    .map(|line| render_line(&text1.words, &colors1, *line))
+                    .unwrap_or_default();
+                let (line2, _) = lines2
+                    .get(i)
+                    .map(|line| render_line(&text2.words, &colors2, *line))
+                    .unwrap_or_default();
+                println!("{}{}{}", pad(line1, len1), COLUMN_SEPARATOR, line2);
+            }
+            println!();
+        }
+    }
+}
+
+/// Percentage of the words of both texts together that are in matching text,
+///     given the percentage of each text that matches and its number of words
+fn similarity((percent1, words1): (f32, usize), (percent2, words2): (f32, usize)) -> f32 {
+    if words1 + words2 == 0 {
+        return 0.0;
+    }
+    (percent1 * words1 as f32 + percent2 * words2 as f32) / (words1 + words2) as f32
+}
+
+/// Works out which passage (if any) each word of both texts belongs to. Overlapping
+///     matches in the first text are merged into passages, and the words of the
+///     second text matching them get the same passage.
+fn passage_colors(
+    len1: usize,
+    len2: usize,
+    locations: &[(Vec<FragmentLocation>, Vec<FragmentLocation>)],
+) -> (Vec<Option<usize>>, Vec<Option<usize>>) {
+    let mut passages: Vec<FragmentLocation> = locations
+        .iter()
+        .flat_map(|(locations1, _)| locations1.iter().copied())
+        .collect();
+    passages.sort_unstable();
+    passages.dedup_by(|next, merged| {
+        if next.0 <= merged.1 + 1 {
+            merged.1 = merged.1.max(next.1);
+            true
+        } else {
+            false
+        }
+    });
+    let passage_of = |word: usize| {
+        passages
+            .iter()
+            .position(|(start, end)| *start <= word && word <= *end)
+    };
+
+    let mut colors1: Vec<Option<usize>> = vec![None; len1];
+    for (i, (start, end)) in passages.iter().enumerate() {
+        for color in colors1.iter_mut().take(*end + 1).skip(*start) {
+            *color = Some(i);
+        }
+    }
+    let mut colors2: Vec<Option<usize>> = vec![None; len2];
+    for (locations1, locations2) in locations {
+        let passage = locations1.first().and_then(|(start, _)| passage_of(*start));
+        for (start, end) in locations2 {
+            for color in colors2.iter_mut().take(*end + 1).skip(*start) {
+                *color = color.or(passage);
+            }
+        }
+    }
+    (colors1, colors2)
+}
+
+/// Splits words into lines of at most the width (unless a word is longer),
+///     given as (first word, one past the last word)
+fn wrap(words: &[String], width: usize) -> Vec<(usize, usize)> {
+    let mut lines: Vec<(usize, usize)> = Vec::new();
+    let mut start = 0;
+    let mut len = 0;
+    for (i, word) in words.iter().enumerate() {
+        let word_len = word.chars().count();
+        if i > start && len + 1 + word_len > width {
+            lines.push((start, i));
+            start = i;
+            len = 0;
+        }
+        len += if i > start { 1 + word_len } else { word_len };
+    }
+    if start < words.len() {
+        lines.push((start, words.len()));
+    }
+    lines
+}
+
+/// Joins a line of words, colouring the ones in matching passages.
+///     Returns the line and its length without the colour codes.
+fn render_line(
+    words: &[String],
+    colors: &[Option<usize>],
+    (start, end): (usize, usize),
+) -> (String, usize) {
+    let line: Vec<String> = (start..end)
+        .map(|i| match colors[i] {
+            Some(passage) => words[i]
+                .color(PASSAGE_COLORS[passage % PASSAGE_COLORS.len()])
+                .bold()
+                .to_string(),
+            None => words[i].clone(),
+        })
+        .collect();
+    let len = words[start..end]
+        .iter()
+        .map(|word| word.chars().count())
+        .sum::<usize>()
+        + (end - start).saturating_sub(1);
+    (line.join(" "), len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passage_colors() {
+        // Two overlapping matches in the first text make one passage
+        let locations = vec![
+            (vec![(0, 2)], vec![(5, 7)]),
+            (vec![(1, 3)], vec![(6, 8)]),
+            (vec![(6, 8)], vec![(0, 2)]),
+        ];
+        let (colors1, colors2) = passage_colors(10, 10, &locations);
+        assert_eq!(
+            colors1,
+            vec![
+                Some(0),
+                Some(0),
+                Some(0),
+                Some(0),
+                None,
+                None,
+                Some(1),
+                Some(1),
+                Some(1),
+                None
+            ]
+        );
+        assert_eq!(colors2[0..3], [Some(1), Some(1), Some(1)]);
+        assert_eq!(colors2[3..5], [None, None]);
+        assert_eq!(colors2[5..9], [Some(0), Some(0), Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn test_wrap() {
+        let words: Vec<String> = "the quick brown fox jumps over the lazy dog"
+            .split(' ')
+            .map(String::from)
+            .collect();
+        assert_eq!(wrap(&words, 15), vec![(0, 3), (3, 6), (6, 9)]);
+        assert_eq!(
+            wrap(&words, 3),
+            (0..9).map(|i| (i, i + 1)).collect::<Vec<_>>()
+        );
+        let colors = vec![None; 9];
+        assert_eq!(
+            render_line(&words, &colors, (0, 3)),
+            ("the quick brown".to_string(), 15)
+        );
+    }
+}
@@ -0,0 +1,565 @@
+#[cfg(any(feature = "rtf", feature = "html"))]
+use crate::encoding_utils::decode_text;
+use std::io::Cursor;
+use std::path::Path;
+
+/// Document formats that plain text can be extracted from
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DocumentKind {
+    Docx,
+    Odt,
+    Rtf,
+    Html,
+    Pdf,
+}
+
+/// Separates the pages of documents that have them (like a form feed in plain text)
+pub const PAGE_SEPARATOR: char = '\x0c';
+
+impl DocumentKind {
+    fn name(self) -> &'static str {
+        match self {
+            DocumentKind::Docx => "DOCX",
+            DocumentKind::Odt => "ODT",
+            DocumentKind::Rtf => "RTF",
+            DocumentKind::Html => "HTML",
+            DocumentKind::Pdf => "PDF",
+        }
+    }
+}
+
+/// Detects if a file is a document by its extension, or failing that by
+///     sniffing its contents. Returns None for plain text files.
+pub fn detect_document_kind(file_name: &str, bytes: &[u8]) -> Option<DocumentKind> {
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    match extension.as_deref() {
+        Some("docx") => return Some(DocumentKind::Docx),
+        Some("odt") => return Some(DocumentKind::Odt),
+        Some("rtf") => return Some(DocumentKind::Rtf),
+        Some("html") | Some("htm") | Some("xhtml") => return Some(DocumentKind::Html),
+        Some("pdf") => return Some(DocumentKind::Pdf),
+        _ => {}
+    }
+
+    if bytes.starts_with(b"%PDF-") {
+        return Some(DocumentKind::Pdf);
+    }
+    if bytes.starts_with(b"{\\rtf") {
+        return Some(DocumentKind::Rtf);
+    }
+    if bytes.starts_with(b"PK\x03\x04") {
+        let archive = zip::ZipArchive::new(Cursor::new(bytes)).ok()?;
+        let names: Vec<&str> = archive.file_names().collect();
+        if names.contains(&"word/document.xml") {
+            return Some(DocumentKind::Docx);
+        } else if names.contains(&"content.xml") {
+            return Some(DocumentKind::Odt);
+        }
+        return None;
+    }
+    let start: Vec<u8> = bytes
+        .iter()
+        .skip_while(|b| b.is_ascii_whitespace())
+        .take(15)
+        .map(|b| b.to_ascii_lowercase())
+        .collect();
+    if start.starts_with(b"<!doctype html") || start.starts_with(b"<html") {
+        return Some(DocumentKind::Html);
+    }
+    None
+}
+
+/// Extracts the plain text of a document, or the reason it couldn't be
+///     extracted (e.g. support for the format was not compiled in).
+///     Pages are separated by PAGE_SEPARATOR.
+#[cfg_attr(
+    not(any(feature = "office", feature = "rtf", feature = "html", feature = "pdf")),
+    allow(unused_variables)
+)]
+pub fn extract_document_text(kind: DocumentKind, bytes: &[u8]) -> Result<String, String> {
+    match kind {
+        #[cfg(feature = "office")]
+        DocumentKind::Docx => extract_office_text(bytes, "word/document.xml", &DOCX_XML),
+        #[cfg(feature = "office")]
+        DocumentKind::Odt => extract_office_text(bytes, "content.xml", &ODT_XML),
+        #[cfg(feature = "rtf")]
+        DocumentKind::Rtf => decode_text(bytes).map(|text| extract_rtf_text(&text)),
+        #[cfg(feature = "html")]
+        DocumentKind::Html => decode_text(bytes).map(|text| extract_html_text(&text)),
+        #[cfg(feature = "pdf")]
+        DocumentKind::Pdf => extract_pdf_text(bytes),
+        #[allow(unreachable_patterns)]
+        _ => Err(format!(
+            "is a {} document, but this build does not support {} documents",
+            kind.name(),
+            kind.name()
+        )),
+    }
+}
+
+/// Which XML elements hold the text of an office document
+#[cfg(feature = "office")]
+struct OfficeXml {
+    /// If set, only text inside these elements is kept
+    text_elements: Option<&'static [&'static [u8]]>,
+    /// Elements that end a line when they close (paragraphs, line breaks)
+    line_elements: &'static [&'static [u8]],
+    /// Elements that stand for whitespace (tabs, spaces)
+    space_elements: &'static [&'static [u8]],
+}
+
+#[cfg(feature = "office")]
+impl OfficeXml {
+    fn is_text_element(&self, name: &[u8]) -> bool {
+        self.text_elements
+            .is_some_and(|elements| elements.contains(&name))
+    }
+}
+
+#[cfg(feature = "office")]
+const DOCX_XML: OfficeXml = OfficeXml {
+    text_elements: Some(&[b"t"]),
+    line_elements: &[b"p", b"br", b"cr"],
+    space_elements: &[b"tab"],
+};
+
+#[cfg(feature = "office")]
+const ODT_XML: OfficeXml = OfficeXml {
+    text_elements: None,
+    line_elements: &[b"p", b"h", b"line-break"],
+    space_elements: &[b"s", b"tab"],
+};
+
+/// Reads the XML file holding the body of a zipped office document and
+///     keeps only its text
+#[cfg(feature = "office")]
+fn extract_office_text(bytes: &[u8], xml_path: &str, xml: &OfficeXml) -> Result<String, String> {
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+    use std::io::Read;
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|err| format!("is not a valid document archive: {}", err))?;
+    let mut contents: Vec<u8> = Vec::new();
+    archive
+        .by_name(xml_path)
+        .map_err(|_| format!("is missing {}", xml_path))?
+        .read_to_end(&mut contents)
+        .map_err(|err| format!("cannot read {}: {}", xml_path, err))?;
+
+    let mut reader = Reader::from_reader(contents.as_slice());
+    let mut buf: Vec<u8> = Vec::new();
+    let mut text = String::new();
+    let mut text_depth = 0;
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                let name = e.local_name();
+                if xml.is_text_element(name.as_ref()) {
+                    text_depth += 1;
+                }
+            }
+            Ok(Event::Empty(e)) => {
+                let name = e.local_name();
+                if xml.line_elements.contains(&name.as_ref()) {
+                    text.push('\n');
+                } else if xml.space_elements.contains(&name.as_ref()) {
+                    text.push(' ');
+                }
+            }
+            Ok(Event::End(e)) => {
+                let name = e.local_name();
+                if xml.is_text_element(name.as_ref()) {
+                    text_depth -= 1;
+                } else if xml.line_elements.contains(&name.as_ref()) {
+                    text.push('\n');
+                }
+            }
+            Ok(Event::Text(e)) if xml.text_elements.is_none() || text_depth > 0 => {
+                let unescaped = e
+                    .unescape()
+                    .map_err(|err| format!("has invalid XML in {}: {}", xml_path, err))?;
+                text.push_str(&unescaped);
+            }
+            Ok(Event::Eof) => break,
+            Err(err) => return Err(format!("has invalid XML in {}: {}", xml_path, err)),
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(text)
+}
+
+/// RTF groups whose contents are not part of the document text
+#[cfg(feature = "rtf")]
+const RTF_SKIPPED_DESTINATIONS: [&str; 9] = [
+    "fonttbl",
+    "colortbl",
+    "stylesheet",
+    "info",
+    "pict",
+    "header",
+    "footer",
+    "listtable",
+    "listoverridetable",
+];
+
+/// Keeps only the text of an RTF document, dropping control words and
+///     formatting groups
+#[cfg(feature = "rtf")]
+fn extract_rtf_text(rtf: &str) -> String {
+    let chars: Vec<char> = rtf.chars().collect();
+    let mut text = String::new();
+    // One entry per open group: whether the group's contents are skipped
+    let mut skip_stack: Vec<bool> = vec![false];
+    let mut i = 0;
+    while i < chars.len() {
+        let skipping = *skip_stack.last().unwrap_or(&false);
+        match chars[i] {
+            '{' => {
+                skip_stack.push(skipping);
+                i += 1;
+            }
+            '}' => {
+                skip_stack.pop();
+                i += 1;
+            }
+            '\\' => {
+                i += 1;
+                let c = match chars.get(i) {
+                    Some(c) => *c,
+                    None => break,
+                };
+                if c.is_ascii_alphabetic() {
+                    let word_start = i;
+                    while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                        i += 1;
+                    }
+                    let word: String = chars[word_start..i].iter().collect();
+                    let param_start = i;
+                    if i < chars.len() && chars[i] == '-' {
+                        i += 1;
+                    }
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                    let param: Option<i32> = chars[param_start..i]
+                        .iter()
+                        .collect::<String>()
+                        .parse()
+                        .ok();
+                    // A single space ends a control word and is not part of the text
+                    if i < chars.len() && chars[i] == ' ' {
+                        i += 1;
+                    }
+                    if RTF_SKIPPED_DESTINATIONS.contains(&word.as_str()) {
+                        if let Some(last) = skip_stack.last_mut() {
+                            *last = true;
+                        }
+                    } else if !skipping {
+                        match word.as_str() {
+                            "par" | "line" | "sect" | "page" => text.push('\n'),
+                            "tab" => text.push(' '),
+                            "u" => {
+                                if let Some(c) = param
+                                    .map(|p| if p < 0 { p + 65536 } else { p } as u32)
+                                    .and_then(std::char::from_u32)
+                                {
+                                    text.push(c);
+                                }
+                                // Skip the fallback character for readers without unicode
+                                if chars.get(i) == Some(&'\\') && chars.get(i + 1) == Some(&'\'') {
+                                    i += 4;
+                                } else if i < chars.len() && !"{}\\".contains(chars[i]) {
+                                    i += 1;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                } else if c == '*' {
+                    // Optional destinations that readers may ignore
+                    if let Some(last) = skip_stack.last_mut() {
+                        *last = true;
+                    }
+                    i += 1;
+                } else if c == '\'' {
+                    let hex: String = chars.iter().skip(i + 1).take(2).collect();
+                    if !skipping {
+                        if let Ok(byte) = u8::from_str_radix(&hex, 16) {
+                            let bytes = [byte];
+                            let (decoded, _, _) = encoding_rs::WINDOWS_1252.decode(&bytes);
+                            text.push_str(&decoded);
+                        }
+                    }
+                    i += 3;
+                } else {
+                    // Escaped symbol such as \{ or \\, or a line break after a backslash
+                    if !skipping && "{}\\".contains(c) {
+                        text.push(c);
+                    } else if !skipping && (c == '\n' || c == '\r') {
+                        text.push('\n');
+                    }
+                    i += 1;
+                }
+            }
+            '\n' | '\r' => i += 1,
+            c => {
+                if !skipping {
+                    text.push(c);
+                }
+                i += 1;
+            }
+        }
+    }
+    text
+}
+
+/// Keeps only the visible text of an HTML page
+#[cfg(feature = "html")]
+fn extract_html_text(html: &str) -> String {
+    use lazy_static::lazy_static;
+    use regex::Regex;
+
+    lazy_static! {
+        static ref INVISIBLE: Regex =
+            Regex::new(r"(?is)<!--.*?-->|<(script|style|head)\b.*?</(script|style|head)\s*>")
+                .expect("Regex to remove invisible HTML could not be compiled properly!");
+        static ref LINE_BREAKS: Regex =
+            Regex::new(r"(?i)<br\s*/?>|</(p|div|li|tr|h[1-6]|blockquote|pre)\s*>")
+                .expect("Regex to find HTML line breaks could not be compiled properly!");
+        static ref TAGS: Regex = Regex::new(r"(?s)<[^>]*>")
+            .expect("Regex to remove HTML tags could not be compiled properly!");
+        static ref ENTITIES: Regex = Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);")
+            .expect("Regex to find HTML entities could not be compiled properly!");
+    }
+    let text = INVISIBLE.replace_all(html, " ");
+    let text = LINE_BREAKS.replace_all(&text, "\n");
+    let text = TAGS.replace_all(&text, " ");
+    ENTITIES
+        .replace_all(&text, |captures: &regex::Captures| {
+            let entity = &captures[1];
+            let decoded = if let Some(hex) = entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                u32::from_str_radix(hex, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+            } else if let Some(decimal) = entity.strip_prefix('#') {
+                decimal.parse().ok().and_then(std::char::from_u32)
+            } else {
+                match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    _ => None,
+                }
+            };
+            decoded
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| captures[0].to_string())
+        })
+        .to_string()
+}
+
+/// Extracts the text of each page of a PDF, without the headers, footers and
+///     page numbers that are repeated on every page
+#[cfg(feature = "pdf")]
+fn extract_pdf_text(bytes: &[u8]) -> Result<String, String> {
+    // pdf-extract panics on some malformed documents instead of returning an error
+    let pages = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(bytes))
+        .map_err(|_| "could not be parsed as a PDF".to_string())?
+        .map_err(|err| format!("could not be parsed as a PDF: {}", err))?;
+    Ok(strip_repeated_page_lines(&pages).join(&PAGE_SEPARATOR.to_string()))
+}
+
+/// How many lines at the top and bottom of each page may be a header or footer
+#[cfg(feature = "pdf")]
+const PAGE_MARGIN_LINES: usize = 2;
+
+/// Removes header and footer lines from pages. A line near the top or bottom
+///     of a page is a header/footer if it is only a page number, or if the same
+///     line (ignoring digits, e.g. "Page 3 of 10") is near the top or bottom of
+///     at least half of the pages.
+#[cfg(feature = "pdf")]
+fn strip_repeated_page_lines(pages: &[String]) -> Vec<String> {
+    use std::collections::{HashMap, HashSet};
+
+    let pages_lines: Vec<Vec<&str>> = pages
+        .iter()
+        .map(|page| {
+            page.lines()
+                .filter(|line| !line.trim().is_empty())
+                .collect()
+        })
+        .collect();
+    // Short pages only have their first and last line checked, so that their
+    // body text isn't mistaken for a header or footer
+    let margin_indices = |num_lines: usize| -> Vec<usize> {
+        let margin = if num_lines > 2 * PAGE_MARGIN_LINES {
+            PAGE_MARGIN_LINES
+        } else {
+            1
+        };
+        (0..num_lines)
+            .filter(|i| *i < margin || *i + margin >= num_lines)
+            .collect()
+    };
+
+    let mut margin_line_counts: HashMap<String, usize> = HashMap::new();
+    for lines in &pages_lines {
+        let keys: HashSet<String> = margin_indices(lines.len())
+            .into_iter()
+            .map(|i| page_line_key(lines[i]))
+            .collect();
+        for key in keys {
+            *margin_line_counts.entry(key).or_insert(0) += 1;
+        }
+    }
+    let is_repeated = |line: &str| {
+        pages.len() >= 3 && margin_line_counts[&page_line_key(line)] * 2 >= pages.len()
+    };
+
+    pages_lines
+        .iter()
+        .map(|lines| {
+            let margins: HashSet<usize> = margin_indices(lines.len()).into_iter().collect();
+            lines
+                .iter()
+                .enumerate()
+                .filter(|(i, line)| {
+                    !margins.contains(i) || !(is_page_number(line) || is_repeated(line))
+                })
+                .map(|(_, line)| *line)
+                .collect::<Vec<&str>>()
+                .join("\n")
+        })
+        .collect()
+}
+
+/// Normalizes a line for comparison between pages, ignoring numbers and spacing
+#[cfg(feature = "pdf")]
+fn page_line_key(line: &str) -> String {
+    line.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| if c.is_ascii_digit() { '#' } else { c })
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Lines such as "4", "- 4 -", "Page 4" or "page 4 of 10"
+#[cfg(feature = "pdf")]
+fn is_page_number(line: &str) -> bool {
+    let words: Vec<String> = line
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+    words
+        .iter()
+        .any(|word| word.chars().all(|c| c.is_ascii_digit()))
+        && words
+            .iter()
+            .all(|word| word == "page" || word == "of" || word.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_document_kind() {
+        assert_eq!(
+            detect_document_kind("essay.DOCX", b""),
+            Some(DocumentKind::Docx)
+        );
+        assert_eq!(
+            detect_document_kind("essay", b"{\\rtf1\\ansi hello}"),
+            Some(DocumentKind::Rtf)
+        );
+        assert_eq!(
+            detect_document_kind("page", b"\n <!DOCTYPE html><html></html>"),
+            Some(DocumentKind::Html)
+        );
+        assert_eq!(
+            detect_document_kind("upload", b"%PDF-1.4\n"),
+            Some(DocumentKind::Pdf)
+        );
+        assert_eq!(detect_document_kind("essay.txt", b"hello"), None);
+    }
+
+    #[cfg(feature = "rtf")]
+    #[test]
+    fn test_extract_rtf() {
+        let rtf = "{\\rtf1\\ansi{\\fonttbl{\\f0 Times;}}{\\*\\generator Word;}\\f0 Caf\\'e9 \\b bold\\b0\\par\nNext line \\u8212?dash\\}}";
+        assert_eq!(extract_rtf_text(rtf), "Café bold\nNext line \u{2014}dash}");
+    }
+
+    #[cfg(feature = "html")]
+    #[test]
+    fn test_extract_html() {
+        let html = "<html><head><title>Skip</title></head><body><p>Fish &amp; chips</p><script>var x;</script>tea&#33;</body></html>";
+        assert_eq!(
+            crate::text_utils::clean_text(&extract_html_text(html)),
+            vec!["fish", "chips", "tea"]
+        );
+    }
+
+    #[cfg(feature = "office")]
+    #[test]
+    fn test_extract_docx() {
+        use std::io::Write;
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("word/document.xml", zip::write::FileOptions::default())
+            .unwrap();
+        writer
+            .write_all(
+                b"<w:document xmlns:w=\"w\"><w:body><w:p><w:r><w:t>Hello</w:t></w:r><w:r><w:tab/><w:t xml:space=\"preserve\">world &amp; all</w:t></w:r></w:p><w:p><w:r><w:t>Bye</w:t></w:r></w:p></w:body></w:document>",
+            )
+            .unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        assert_eq!(
+            detect_document_kind("upload", &bytes),
+            Some(DocumentKind::Docx)
+        );
+        assert_eq!(
+            extract_document_text(DocumentKind::Docx, &bytes),
+            Ok("Hello world & all\nBye\n".to_string())
+        );
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn test_strip_repeated_page_lines() {
+        let bodies = ["It was the best", "of times, it was", "the worst of times"];
+        let pages: Vec<String> = bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| {
+                format!(
+                    "CS101 Essay - Alice\n{}\n\n{}\n{}\nPage {} of 3",
+                    body,
+                    body.to_uppercase(),
+                    body.len(),
+                    i + 1
+                )
+            })
+            .collect();
+        assert_eq!(
+            strip_repeated_page_lines(&pages),
+            vec![
+                "It was the best\nIT WAS THE BEST",
+                "of times, it was\nOF TIMES, IT WAS",
+                "the worst of times\nTHE WORST OF TIMES",
+            ]
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// Share of characters that may be control characters before a file is
+///     considered binary rather than text
+const MAX_CONTROL_CHAR_RATIO: f32 = 0.05;
+
+/// Decodes the contents of a file into a UTF-8 string.
+///     The encoding is taken from a byte order mark if there is one, otherwise
+///     UTF-16 is detected by its zero bytes, then UTF-8 is tried and finally
+///     a heuristic guess (e.g. Windows-1252 or Shift_JIS).
+///     Returns the reason the file was not decoded if it looks binary or
+///     contains bytes that are invalid in the detected encoding.
+pub fn decode_text(bytes: &[u8]) -> Result<String, String> {
+    let encoding = detect_encoding(bytes);
+    // decode() also strips any byte order mark
+    let (text, _, had_errors) = encoding.decode(bytes);
+    if had_errors {
+        return Err(format!(
+            "contains bytes that are not valid {}",
+            encoding.name()
+        ));
+    }
+    if looks_binary(&text) {
+        return Err("appears to be a binary file, not text".to_string());
+    }
+    Ok(text.into_owned())
+}
+
+fn detect_encoding(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    // ASCII text in UTF-16 is also valid UTF-8 (with NULs), so check this first
+    if let Some(encoding) = detect_utf16_without_bom(bytes) {
+        return encoding;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+/// Mostly-ASCII UTF-16 text has a zero byte in every other position
+fn detect_utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let pairs = bytes.len() / 2;
+    let even_zeros = bytes.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_zeros = bytes.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    if odd_zeros * 2 > pairs && even_zeros * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even_zeros * 2 > pairs && odd_zeros * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Binary files decode to text full of NULs and other control characters
+fn looks_binary(text: &str) -> bool {
+    let mut total = 0;
+    let mut control = 0;
+    for c in text.chars() {
+        total += 1;
+        if c == '\0' {
+            return true;
+        }
+        if c.is_control() && !c.is_whitespace() {
+            control += 1;
+        }
+    }
+    total > 0 && (control as f32) / (total as f32) > MAX_CONTROL_CHAR_RATIO
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_utf8_with_bom() {
+        assert_eq!(
+            decode_text(b"\xEF\xBB\xBFcaf\xC3\xA9"),
+            Ok("café".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_utf16() {
+        let with_bom: Vec<u8> = vec![0xFF, 0xFE, b'h', 0, b'i', 0];
+        assert_eq!(decode_text(&with_bom), Ok("hi".to_string()));
+        let without_bom: Vec<u8> = "hello there"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes().to_vec())
+            .collect();
+        assert_eq!(decode_text(&without_bom), Ok("hello there".to_string()));
+    }
+
+    #[test]
+    fn test_decode_windows_1252() {
+        assert_eq!(
+            decode_text(b"The caf\xE9 served cr\xE8me br\xFBl\xE9e"),
+            Ok("The café served crème brûlée".to_string())
+        );
+    }
+
+    #[test]
+    fn test_reject_binary() {
+        assert!(decode_text(b"\x7FELF\x02\x01\x01\x00\x00\x00\x00").is_err());
+    }
+}
@@ -1,46 +1,698 @@
+use crate::archive_utils::{
+    is_archive, read_archive_entries, strip_archive_extension, ArchiveEntry,
+};
+use crate::document_utils::{detect_document_kind, extract_document_text};
+use crate::encoding_utils::decode_text;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// What to do with symbolic links found while scanning a directory
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SymlinkPolicy {
+    /// Read the files (and directories) that links point to
+    Follow,
+    /// Ignore links entirely
+    Skip,
+}
+
+/// Controls which files are picked up when scanning a directory
+#[derive(Clone, Debug)]
+pub struct ScanOptions {
+    /// Descend into subdirectories instead of only reading top-level files
+    pub recursive: bool,
+    /// Glob patterns matched against paths relative to the scanned directory.
+    ///     Patterns starting with ! exclude matching files, the rest include them.
+    ///     If there are no include patterns, every file is included.
+    pub globs: Vec<String>,
+    pub symlinks: SymlinkPolicy,
+    /// Read files and directories whose names start with a dot
+    pub include_hidden: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> ScanOptions {
+        ScanOptions {
+            recursive: false,
+            globs: Vec::new(),
+            symlinks: SymlinkPolicy::Follow,
+            include_hidden: false,
+        }
+    }
+}
+
+/// One owner's submission, made up of one or more files
+#[derive(Clone, Debug)]
+pub struct Submission {
+    pub owner: String,
+    /// (file name, file contents) for each file in the submission
+    pub files: Vec<(String, String)>,
+}
+
+/// A file that was found but could not be read as text
+#[derive(Serialize, Clone, Debug)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Reads every submission in a directory or archive, along with any files that
+///     had to be skipped.
+///     If submission_dirs is false, each file is a submission of its own.
+///     Otherwise each top-level subdirectory is a single submission made up of
+///     all files inside it (at any depth), named relative to that subdirectory.
+///     Top-level files are still treated as submissions of their own.
+pub fn get_submissions(
+    path: &str,
+    options: &ScanOptions,
+    submission_dirs: bool,
+) -> (Vec<Submission>, Vec<SkippedFile>) {
+    if !submission_dirs {
+        let (file_contents, skipped) = get_file_contents(path, options);
+        let submissions = file_contents
+            .into_iter()
+            .map(|(id, contents)| Submission {
+                owner: id.clone(),
+                files: vec![(id, contents)],
+            })
+            .collect();
+        return (submissions, skipped);
+    }
+
+    let recursive_options = ScanOptions {
+        recursive: true,
+        ..options.clone()
+    };
+    let (file_contents, skipped) = get_file_contents(path, &recursive_options);
+    let mut submissions: Vec<Submission> = Vec::new();
+    // Index of each owner's submission in submissions
+    let mut owner_indices: HashMap<String, usize> = HashMap::new();
+    for (id, contents) in file_contents {
+        let path = PathBuf::from(&id);
+        let mut components = path.components();
+        let owner = components
+            .next()
+            .expect("Scanned file has an empty path!")
+            .as_os_str()
+            .to_string_lossy()
+            .to_string();
+        let file_name = components.as_path().to_string_lossy().to_string();
+        let file_name = if file_name.is_empty() {
+            owner.clone()
+        } else {
+            file_name
+        };
+        match owner_indices.get(&owner) {
+            Some(&index) => submissions[index].files.push((file_name, contents)),
+            None => {
+                owner_indices.insert(owner.clone(), submissions.len());
+                submissions.push(Submission {
+                    owner,
+                    files: vec![(file_name, contents)],
+                });
+            }
+        }
+    }
+    (submissions, skipped)
+}
+
+/// Reads every submission in an archive of past terms, where each top-level
+///     subdirectory holds the submissions of one term (e.g. archive/2023S1/alice.txt).
+///     Returns (term, submission) pairs, with each owner named after its term
+///     (e.g. 2023S1/alice.txt) so that it doesn't clash with current submissions.
+///     Panics if the directory cannot be read.
+pub fn get_archived_submissions(
+    path: &str,
+    options: &ScanOptions,
+    submission_dirs: bool,
+) -> (Vec<(String, Submission)>, Vec<SkippedFile>) {
+    let mut terms: Vec<(String, PathBuf)> = fs::read_dir(path)
+        .unwrap_or_else(|err| panic!("Cannot read archive directory {}: {}", path, err))
+        .map(|entry| {
+            entry.unwrap_or_else(|err| panic!("Cannot read archive directory {}: {}", path, err))
+        })
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| {
+            (
+                entry.file_name().to_string_lossy().to_string(),
+                entry.path(),
+            )
+        })
+        .filter(|(term, _)| options.include_hidden || !is_hidden_name(term))
+        .collect();
+    terms.sort();
+
+    let mut archived: Vec<(String, Submission)> = Vec::new();
+    let mut skipped: Vec<SkippedFile> = Vec::new();
+    for (term, term_path) in terms {
+        let (submissions, mut term_skipped) =
+            get_submissions(&term_path.to_string_lossy(), options, submission_dirs);
+        skipped.append(&mut term_skipped);
+        for submission in submissions {
+            let owner = format!("{}/{}", term, submission.owner);
+            archived.push((
+                term.clone(),
+                Submission {
+                    owner,
+                    files: submission.files,
+                },
+            ));
+        }
+    }
+    (archived, skipped)
+}
 
 /// Returns a vector of tuples of (file name, file contents as string)
-///     for each file in the directory.
-///     Panics on error if it's a filename error
-///     Silently replaces file contents by error string otherwise.
-pub fn get_file_contents_from_dir(dir: &str) -> Vec<(String, String)> {
-    let filepaths = get_file_paths_from_dir(dir);
+///     for each file in the directory or archive (.zip, .tar, .tar.gz) at the path.
+///     Files in subdirectories are named by their path relative to the directory
+///     so that files with the same name don't clash.
+///     Archives inside a directory are read as if they were a subdirectory named
+///     after the archive, and every file in an archive is read even if not recursive.
+///     Text is extracted from documents (DOCX, ODT, RTF and HTML), and
+///     files in other encodings are transcoded to UTF-8. Files that can't be
+///     decoded as text (or archives that can't be read) are returned separately
+///     with the reason they were skipped.
+///     Panics on error if it's a filename error.
+pub fn get_file_contents(
+    path: &str,
+    options: &ScanOptions,
+) -> (Vec<(String, String)>, Vec<SkippedFile>) {
+    let (includes, excludes) = build_globsets(&options.globs);
     let mut file_id_contents: Vec<(String, String)> = Vec::new();
-    for filepath in filepaths {
+    let mut skipped: Vec<SkippedFile> = Vec::new();
+    for (file_path_str, bytes) in get_file_bytes(path, options) {
+        // An empty path stands for the archive at the path itself
+        let relative_path = Path::new(&file_path_str);
+        if !file_path_str.is_empty()
+            && ((!options.include_hidden && has_hidden_component(relative_path))
+                || excludes.is_match(relative_path)
+                || (!includes.is_empty() && !includes.is_match(relative_path)))
+        {
+            continue;
+        }
+        let decoded = bytes.and_then(|bytes| match detect_document_kind(&file_path_str, &bytes) {
+            Some(kind) => extract_document_text(kind, &bytes),
+            None => decode_text(&bytes),
+        });
+        match decoded {
+            Ok(contents) => file_id_contents.push((file_path_str, contents)),
+            Err(reason) => skipped.push(SkippedFile {
+                path: Path::new(path)
+                    .join(file_path_str)
+                    .to_string_lossy()
+                    .to_string(),
+                reason,
+            }),
+        }
+    }
+    (file_id_contents, skipped)
+}
+
+/// Reads a single file as a submission of the given owner, extracting text from
+///     documents and transcoding it like get_file_contents.
+///     Panics if the file cannot be read or decoded as text.
+pub fn get_single_file(path: &str, owner: &str) -> Submission {
+    let bytes =
+        fs::read(path).unwrap_or_else(|err| panic!("File {} cannot be read: {}", path, err));
+    let decoded = match detect_document_kind(path, &bytes) {
+        Some(kind) => extract_document_text(kind, &bytes),
+        None => decode_text(&bytes),
+    };
+    let contents =
+        decoded.unwrap_or_else(|reason| panic!("File {} cannot be read as text: {}", path, reason));
+    Submission {
+        owner: owner.to_string(),
+        files: vec![(path.to_string(), contents)],
+    }
+}
+
+/// Reads every file in a directory or archive as (relative path, raw contents),
+///     or the reason a file in an archive (or an archive) couldn't be read.
+///     If the archive at the path can't be read, its path is empty.
+fn get_file_bytes(path: &str, options: &ScanOptions) -> Vec<ArchiveEntry> {
+    if !Path::new(path).is_dir() {
+        if !is_archive(path) || !Path::new(path).is_file() {
+            panic!(
+                "Path {} doesn't exist, no permissions, or is not a directory or archive!",
+                path
+            );
+        }
+        let bytes = fs::read(path).unwrap_or_else(|_| panic!("Archive {} cannot be read!", path));
+        return read_archive_entries(path, &bytes)
+            .unwrap_or_else(|reason| vec![(String::new(), Err(reason))]);
+    }
+
+    let mut entries: Vec<ArchiveEntry> = Vec::new();
+    for filepath in get_file_paths_from_dir(path, options) {
         let file_path_str = filepath
-            .file_name()
-            .expect("Invalid file path! (likely terminating in /..)")
+            .strip_prefix(path)
+            .expect("Scanned file is not inside the scanned directory!")
             .to_str()
             .expect("File name could not be converted from an OsStr to a String!")
             .to_string();
-        file_id_contents.push((
-            file_path_str.clone(),
-            fs::read_to_string(filepath).unwrap_or_else(|_| {
-                panic!(
-                    "{} cannot be read as an UTF-8 file! Please ensure it is in the UTF-8 format.",
-                    file_path_str
-                )
-            }),
-        ))
+        let bytes =
+            fs::read(&filepath).unwrap_or_else(|_| panic!("{} cannot be read!", file_path_str));
+        if is_archive(&file_path_str) {
+            let folder = strip_archive_extension(&file_path_str);
+            match read_archive_entries(&file_path_str, &bytes) {
+                Ok(archive_entries) => {
+                    for (inner_path, contents) in archive_entries {
+                        entries.push((format!("{}/{}", folder, inner_path), contents));
+                    }
+                }
+                Err(reason) => entries.push((file_path_str, Err(reason))),
+            }
+        } else {
+            entries.push((file_path_str, Ok(bytes)));
+        }
     }
-    file_id_contents
+    entries
 }
 
-/// Returns a list of paths to files (not subdirectories) that are in a directory
-fn get_file_paths_from_dir(dir: &str) -> Vec<PathBuf> {
+/// Returns a sorted list of paths to files (not directories) in a directory
+///     that pass the scan options. Glob patterns are checked later so that
+///     they also apply to files inside archives.
+fn get_file_paths_from_dir(dir: &str, options: &ScanOptions) -> Vec<PathBuf> {
+    let walker = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(if options.recursive { usize::MAX } else { 1 })
+        .follow_links(options.symlinks == SymlinkPolicy::Follow)
+        .sort_by_file_name();
+
     let mut paths: Vec<PathBuf> = Vec::new();
-    for entry in fs::read_dir(dir).unwrap_or_else(|_| {
-        panic!(
-            "Path {} doesn't exist, no permissions, or is not a directory!",
-            dir
-        )
-    }) {
-        let path = entry.expect("Cannot parse this directory entry!").path();
-        if !path.is_dir() {
-            paths.push(path);
+    for entry in walker
+        .into_iter()
+        .filter_entry(|entry| options.include_hidden || !is_hidden(entry))
+    {
+        let entry = entry.unwrap_or_else(|err| panic!("Cannot read directory entry: {}", err));
+        if entry.file_type().is_dir()
+            || (options.symlinks == SymlinkPolicy::Skip && entry.path_is_symlink())
+        {
+            continue;
         }
+        paths.push(entry.into_path());
     }
     paths
 }
+
+/// Splits glob patterns into (include, exclude) sets, where exclude patterns start with !
+fn build_globsets(globs: &[String]) -> (GlobSet, GlobSet) {
+    let mut includes = GlobSetBuilder::new();
+    let mut excludes = GlobSetBuilder::new();
+    for pattern in globs {
+        let (builder, pattern) = match pattern.strip_prefix('!') {
+            Some(excluded) => (&mut excludes, excluded),
+            None => (&mut includes, pattern.as_str()),
+        };
+        builder.add(
+            Glob::new(pattern)
+                .unwrap_or_else(|err| panic!("Invalid glob pattern {}: {}", pattern, err)),
+        );
+    }
+    (
+        includes
+            .build()
+            .expect("Include patterns could not be compiled!"),
+        excludes
+            .build()
+            .expect("Exclude patterns could not be compiled!"),
+    )
+}
+
+/// Checks if a file or directory is hidden (name starts with a dot)
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry
+        .file_name()
+        .to_str()
+        .map(is_hidden_name)
+        .unwrap_or(false)
+}
+
+/// Checks if any part of a relative path is hidden
+fn has_hidden_component(path: &Path) -> bool {
+    path.components()
+        .any(|component| is_hidden_name(&component.as_os_str().to_string_lossy()))
+}
+
+/// Dot files, and the resource fork folders macOS adds to zip files
+fn is_hidden_name(name: &str) -> bool {
+    name.starts_with('.') || name == "__MACOSX"
+}
+
+/// Indicates which language a submission is written in
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Language {
+    /// Natural language text (essays, reports, etc)
+    Text,
+    C,
+    Cpp,
+    Java,
+    Python,
+    Rust,
+    JavaScript,
+}
+
+impl Language {
+    /// Names accepted on the command line, in the same order as the variants
+    pub const NAMES: [&'static str; 7] = ["text", "c", "cpp", "java", "python", "rust", "js"];
+
+    /// Parses a language from its command line name
+    pub fn from_name(name: &str) -> Option<Language> {
+        match name {
+            "text" => Some(Language::Text),
+            "c" => Some(Language::C),
+            "cpp" => Some(Language::Cpp),
+            "java" => Some(Language::Java),
+            "python" => Some(Language::Python),
+            "rust" => Some(Language::Rust),
+            "js" => Some(Language::JavaScript),
+            _ => None,
+        }
+    }
+
+    /// Returns the command line name of this language
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Text => "text",
+            Language::C => "c",
+            Language::Cpp => "cpp",
+            Language::Java => "java",
+            Language::Python => "python",
+            Language::Rust => "rust",
+            Language::JavaScript => "js",
+        }
+    }
+}
+
+/// Detects the language of a file from its extension, falling back
+///     to looking at the file contents if the extension is missing or unknown
+pub fn detect_language(file_name: &str, contents: &str) -> Language {
+    language_from_extension(file_name).unwrap_or_else(|| language_from_contents(contents))
+}
+
+/// Detects the language of a submission made up of several files.
+///     The most common programming language among the files wins, so that
+///     e.g. a README doesn't turn a Python project into text.
+pub fn detect_submission_language(files: &[(String, String)]) -> Language {
+    let mut counts: Vec<(Language, usize)> = Vec::new();
+    for (file_name, contents) in files {
+        let language = detect_language(file_name, contents);
+        match counts.iter_mut().find(|(counted, _)| *counted == language) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((language, 1)),
+        }
+    }
+    // max_by_key returns the last maximum, so reverse to prefer earlier files on ties
+    counts
+        .into_iter()
+        .rev()
+        .filter(|(language, _)| *language != Language::Text)
+        .max_by_key(|(_, count)| *count)
+        .map(|(language, _)| language)
+        .unwrap_or(Language::Text)
+}
+
+/// Maps well-known file extensions to a language
+fn language_from_extension(file_name: &str) -> Option<Language> {
+    let extension = Path::new(file_name).extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "txt" | "md" | "rst" | "tex" => Some(Language::Text),
+        // Documents (e.g. essays) are prose, whatever words they happen to contain
+        "docx" | "odt" | "rtf" | "html" | "htm" | "xhtml" | "pdf" => Some(Language::Text),
+        "c" | "h" => Some(Language::C),
+        "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Some(Language::Cpp),
+        "java" => Some(Language::Java),
+        "py" | "pyw" => Some(Language::Python),
+        "rs" => Some(Language::Rust),
+        "js" | "mjs" | "cjs" | "jsx" => Some(Language::JavaScript),
+        _ => None,
+    }
+}
+
+/// Number of different telltale signs of a language a file must show to be
+///     treated as code, so that prose mentioning e.g. "import" stays text
+const MIN_CODE_SIGNALS: usize = 2;
+
+/// Guesses the language from a shebang line, or from telltale signs of each
+///     language such as the shape of its definitions and imports.
+///     Anything that doesn't look like code is treated as text.
+fn language_from_contents(contents: &str) -> Language {
+    let first_line = contents.lines().next().unwrap_or("");
+    if first_line.starts_with("#!") {
+        if first_line.contains("python") {
+            return Language::Python;
+        } else if first_line.contains("node") {
+            return Language::JavaScript;
+        }
+    }
+    lazy_static! {
+        static ref CODE_SIGNALS: Vec<(Language, Vec<Regex>)> = [
+            (
+                Language::C,
+                vec![
+                    r#"(?m)^\s*#include\s*[<"]"#,
+                    r"\bint main\s*\(",
+                    r"\b(printf|scanf|malloc|free)\(",
+                    r"(?m)^\s*#define \w+",
+                    r"(?m)^\s*return\b.*;\s*$",
+                ],
+            ),
+            (
+                Language::Java,
+                vec![
+                    r"\bpublic (final |abstract )?class \w+",
+                    r"(?m)^\s*import java\.",
+                    r"\bSystem\.out\.print",
+                    r"\bpublic static void main\s*\(",
+                    r"(?m)^\s*package [\w.]+;",
+                ],
+            ),
+            (
+                Language::Rust,
+                vec![
+                    r"(?m)^\s*(pub )?fn \w+.*\(",
+                    r"\blet mut \w+",
+                    r"(?m)^\s*use [\w:{}, *]+;\s*$",
+                    r"(?m)^\s*impl\b",
+                    r"\bprintln!\(",
+                ],
+            ),
+            (
+                Language::Python,
+                vec![
+                    r"(?m)^\s*def \w+\(.*\)\s*(->.*)?:\s*$",
+                    r"(?m)^\s*(import [\w.]+( as \w+)?|from [\w.]+ import [\w, *]+)\s*$",
+                    r"(?m)^\s*class \w+(\(.*\))?:\s*$",
+                    r"\bself\.\w+",
+                    r"(?m)^\s*(elif .*|else|try|except.*|finally):\s*$",
+                    r"\bprint\(",
+                ],
+            ),
+            (
+                Language::JavaScript,
+                vec![
+                    r"\bfunction\s*\w*\s*\([^)]*\)\s*\{",
+                    r"\bconsole\.log\(",
+                    r"(?m)^\s*(const|let|var) \w+\s*=.*;\s*$",
+                    r"=>\s*\{",
+                    r#"(?m)^\s*(module\.exports|export (default|const|function)|import .* from ['"])"#,
+                ],
+            ),
+        ]
+        .iter()
+        .map(|(language, patterns)| {
+            let regexes = patterns
+                .iter()
+                .map(|pattern| {
+                    Regex::new(pattern)
+                        .expect("Regex to detect a language could not be compiled properly!")
+                })
+                .collect();
+            (*language, regexes)
+        })
+        .collect();
+    }
+    // The language with the most different signs wins, the first listed on ties
+    let best = CODE_SIGNALS
+        .iter()
+        .map(|(language, regexes)| {
+            let signals = regexes
+                .iter()
+                .filter(|regex| regex.is_match(contents))
+                .count();
+            (*language, signals)
+        })
+        .rev()
+        .max_by_key(|(_, signals)| *signals);
+    match best {
+        Some((Language::C, signals)) if signals >= MIN_CODE_SIGNALS => {
+            if contents.contains("std::") || contents.contains("namespace ") {
+                Language::Cpp
+            } else {
+                Language::C
+            }
+        }
+        Some((language, signals)) if signals >= MIN_CODE_SIGNALS => language,
+        _ => Language::Text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a fresh directory tree under the system temp dir for a test
+    fn make_test_dir(name: &str, files: &[&str]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("plagiarismbasic-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "contents").unwrap();
+        }
+        root
+    }
+
+    fn scanned_ids(root: &Path, options: &ScanOptions) -> Vec<String> {
+        get_file_contents(root.to_str().unwrap(), options)
+            .0
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_top_level_only() {
+        let root = make_test_dir("scan-top", &["b.txt", "a.txt", ".DS_Store", "sub/c.txt"]);
+        assert_eq!(
+            scanned_ids(&root, &ScanOptions::default()),
+            vec!["a.txt", "b.txt"]
+        );
+    }
+
+    #[test]
+    fn test_scan_recursive_globs() {
+        let root = make_test_dir(
+            "scan-globs",
+            &[
+                "a.txt",
+                "a.txt~",
+                "sub/c.txt",
+                "node_modules/d.txt",
+                ".git/e.txt",
+            ],
+        );
+        let options = ScanOptions {
+            recursive: true,
+            globs: vec!["**/*.txt".to_string(), "!**/node_modules/**".to_string()],
+            ..ScanOptions::default()
+        };
+        assert_eq!(scanned_ids(&root, &options), vec!["a.txt", "sub/c.txt"]);
+    }
+
+    #[test]
+    fn test_scan_skips_binary_files() {
+        let root = make_test_dir("scan-binary", &["a.txt"]);
+        fs::write(
+            root.join("image.png"),
+            b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR",
+        )
+        .unwrap();
+        let (contents, skipped) =
+            get_file_contents(root.to_str().unwrap(), &ScanOptions::default());
+        assert_eq!(contents.len(), 1);
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].path.ends_with("image.png"));
+    }
+
+    #[test]
+    fn test_scan_submission_dirs() {
+        let root = make_test_dir(
+            "scan-submissions",
+            &[
+                "alice/main.py",
+                "alice/lib/util.py",
+                "bob/essay.txt",
+                "carol.txt",
+            ],
+        );
+        let (submissions, _) =
+            get_submissions(root.to_str().unwrap(), &ScanOptions::default(), true);
+        let owners_files: Vec<(String, Vec<String>)> = submissions
+            .into_iter()
+            .map(|s| (s.owner, s.files.into_iter().map(|(name, _)| name).collect()))
+            .collect();
+        assert_eq!(
+            owners_files,
+            vec![
+                (
+                    "alice".to_string(),
+                    vec!["lib/util.py".to_string(), "main.py".to_string()]
+                ),
+                ("bob".to_string(), vec!["essay.txt".to_string()]),
+                ("carol.txt".to_string(), vec!["carol.txt".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_submission_language_prefers_code() {
+        let files = vec![
+            ("README.txt".to_string(), String::new()),
+            ("main.py".to_string(), String::new()),
+        ];
+        assert_eq!(detect_submission_language(&files), Language::Python);
+    }
+
+    #[test]
+    fn test_language_from_extension() {
+        assert_eq!(detect_language("a.TXT", "def f():\n"), Language::Text);
+        assert_eq!(detect_language("main.cpp", ""), Language::Cpp);
+        assert_eq!(detect_language("lab1.py", ""), Language::Python);
+    }
+
+    #[test]
+    fn test_language_from_contents() {
+        assert_eq!(
+            detect_language("script", "#!/usr/bin/env python3\nprint(1)"),
+            Language::Python
+        );
+        assert_eq!(
+            detect_language("prog", "#include <stdio.h>\nint main() {}"),
+            Language::C
+        );
+        assert_eq!(
+            detect_language("essay", "It was the best of times."),
+            Language::Text
+        );
+        // A single keyword in prose isn't enough to make it code
+        assert_eq!(
+            detect_language(
+                "essay",
+                "The import of grain rose for two reasons:\nthe function of the port changed."
+            ),
+            Language::Text
+        );
+        assert_eq!(
+            detect_language("lab", "import os\n\ndef main():\n    print(os.getcwd())\n"),
+            Language::Python
+        );
+        assert_eq!(
+            detect_language("notes.docx", "def main():\n    import os\n"),
+            Language::Text
+        );
+    }
+
+    #[test]
+    fn test_language_names_roundtrip() {
+        for name in Language::NAMES.iter() {
+            assert_eq!(Language::from_name(name).unwrap().name(), *name);
+        }
+    }
+}
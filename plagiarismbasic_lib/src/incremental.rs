@@ -0,0 +1,342 @@
+use crate::file_utils::{Language, Submission};
+use crate::index::{read_saved_file, write_saved_file};
+use crate::manifest::{GroupMode, Manifest};
+use crate::plagiarism_database::{PlagiarismDatabase, PlagiarismResult, TextOwnerID};
+use crate::text_utils::NORMALIZATION_VERSION;
+use crate::{AppSettings, Metric};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Version of the incremental state file format. Must be increased whenever
+///     anything saved in the state changes shape, so that old states are rejected
+const STATE_FORMAT_VERSION: u32 = 2;
+
+/// Settings that change the results of a check. Results saved with some settings
+///     can't be merged with results checked with others.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct CheckSettings {
+    n: usize,
+    s: usize,
+    metric: Metric,
+    normalization: u32,
+    ignore_metric: Metric,
+    ignore_s: usize,
+    exclude_stopwords: bool,
+    boilerplate_frequency: Option<f32>,
+    group_mode: GroupMode,
+    /// Hash of which group each owner is in, from the manifest
+    groups: u64,
+    submission_dirs: bool,
+    language: Option<Language>,
+    cross_language: bool,
+    split_comments: bool,
+    dedup_sources: bool,
+    comments_n: usize,
+    comments_s: usize,
+}
+
+impl CheckSettings {
+    /// Picks out the settings that change results, including the groups in the manifest
+    pub fn from_app_settings(appsettings: &AppSettings, manifest: &Manifest) -> CheckSettings {
+        CheckSettings {
+            n: appsettings.n,
+            s: appsettings.s,
+            metric: appsettings.metric,
+            normalization: NORMALIZATION_VERSION,
+            ignore_metric: appsettings.ignore_metric,
+            ignore_s: appsettings.ignore_s,
+            exclude_stopwords: appsettings.exclude_stopwords,
+            boilerplate_frequency: appsettings.boilerplate_frequency,
+            group_mode: appsettings.group_mode,
+            groups: hash_groups(&manifest.groups()),
+            submission_dirs: appsettings.submission_dirs,
+            language: appsettings.language,
+            cross_language: appsettings.cross_language,
+            split_comments: appsettings.split_comments,
+            dedup_sources: appsettings.dedup_sources,
+            comments_n: appsettings.comments_n,
+            comments_s: appsettings.comments_s,
+        }
+    }
+
+    /// Whether stopwords were left out of the adjusted plagiarism percentages
+    pub fn exclude_stopwords(&self) -> bool {
+        self.exclude_stopwords
+    }
+}
+
+/// What the last incremental run found, for the next one to carry on from
+pub struct PreviousRun {
+    /// Hash of the files of each untrusted submission, to tell which have changed
+    pub submission_hashes: HashMap<TextOwnerID, u64>,
+    pub untrusted_results: Vec<PlagiarismResult>,
+    pub trusted_results: Vec<PlagiarismResult>,
+    pub archive_results: Vec<PlagiarismResult>,
+}
+
+/// Untrusted submissions that differ from the ones checked on the last run
+pub struct SubmissionChanges {
+    /// Hash of the files of each current untrusted submission
+    pub hashes: HashMap<TextOwnerID, u64>,
+    /// Owners of submissions that are new or have changed
+    pub changed: HashSet<TextOwnerID>,
+    /// Owners of submissions that have been removed
+    pub removed: HashSet<TextOwnerID>,
+}
+
+impl SubmissionChanges {
+    /// Checks if a result from the last run is still valid, i.e. none of the
+    ///     untrusted owners in it has changed or been removed
+    pub fn is_unaffected(&self, result: &PlagiarismResult) -> bool {
+        let is_untrusted_owner1 = !result.trusted_owner1 && result.term1.is_none();
+        let is_unchanged =
+            |owner: &str| !self.changed.contains(owner) && !self.removed.contains(owner);
+        is_unchanged(&result.owner_id2) && (!is_untrusted_owner1 || is_unchanged(&result.owner_id1))
+    }
+}
+
+/// Hashes the names and contents of a submission's files. DefaultHasher may
+///     change between Rust versions, which only means everything is checked again.
+fn hash_submission(submission: &Submission) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    submission.files.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hashes which group each owner is in, in a fixed order
+fn hash_groups(groups: &HashMap<TextOwnerID, String>) -> u64 {
+    let mut groups: Vec<(&TextOwnerID, &String)> = groups.iter().collect();
+    groups.sort();
+    let mut hasher = DefaultHasher::new();
+    groups.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Works out which untrusted submissions were added, changed or removed
+///     since the hashes of the last run were saved
+pub fn find_changes(
+    previous_hashes: &HashMap<TextOwnerID, u64>,
+    submissions: &[Submission],
+) -> SubmissionChanges {
+    let hashes: HashMap<TextOwnerID, u64> = submissions
+        .iter()
+        .map(|submission| (submission.owner.clone(), hash_submission(submission)))
+        .collect();
+    let changed = hashes
+        .iter()
+        .filter(|(owner, hash)| previous_hashes.get(*owner) != Some(hash))
+        .map(|(owner, _)| owner.clone())
+        .collect();
+    let removed = previous_hashes
+        .keys()
+        .filter(|owner| !hashes.contains_key(*owner))
+        .cloned()
+        .collect();
+    SubmissionChanges {
+        hashes,
+        changed,
+        removed,
+    }
+}
+
+/// Saves the databases and results of a run so that the next run only has to
+///     check new submissions. Panics if the file cannot be written.
+pub fn save_state(
+    path: &str,
+    appsettings: &AppSettings,
+    manifest: &Manifest,
+    db: &PlagiarismDatabase,
+    comments_db: &Option<PlagiarismDatabase>,
+    submission_hashes: &HashMap<TextOwnerID, u64>,
+    results: [&[PlagiarismResult]; 3],
+) {
+    let settings = CheckSettings::from_app_settings(appsettings, manifest);
+    write_saved_file(
+        path,
+        "incremental state",
+        STATE_FORMAT_VERSION,
+        &(&settings, db, comments_db, submission_hashes, results),
+    );
+}
+
+/// Reads the settings, databases (one per channel) and results saved by an
+///     incremental run. Panics if the file cannot be read.
+pub fn read_state(
+    path: &str,
+) -> (
+    CheckSettings,
+    PlagiarismDatabase,
+    Option<PlagiarismDatabase>,
+    PreviousRun,
+) {
+    type SavedState = (
+        CheckSettings,
+        PlagiarismDatabase,
+        Option<PlagiarismDatabase>,
+        HashMap<TextOwnerID, u64>,
+        [Vec<PlagiarismResult>; 3],
+    );
+    let (settings, mut db, mut comments_db, submission_hashes, results): SavedState =
+        read_saved_file(path, "incremental state", STATE_FORMAT_VERSION);
+    db.restore_after_load();
+    if let Some(comments_db) = &mut comments_db {
+        comments_db.restore_after_load();
+    }
+    let [untrusted_results, trusted_results, archive_results] = results;
+    let previous_run = PreviousRun {
+        submission_hashes,
+        untrusted_results,
+        trusted_results,
+        archive_results,
+    };
+    (settings, db, comments_db, previous_run)
+}
+
+/// Loads the databases (one per channel) and results saved by the last incremental run.
+///     Panics if the file cannot be read or was saved with different settings
+///     (or groups in the manifest).
+pub fn load_state(
+    path: &str,
+    appsettings: &AppSettings,
+    manifest: &Manifest,
+) -> (PlagiarismDatabase, Option<PlagiarismDatabase>, PreviousRun) {
+    let (settings, db, comments_db, previous_run) = read_state(path);
+    let current_settings = CheckSettings::from_app_settings(appsettings, manifest);
+    if settings != current_settings {
+        panic!(
+            "Incremental state {} was saved with different settings, so its results can't be \
+             reused. Use the same settings and manifest groups, or a new state file.\nSaved: {:?}\nCurrent: {:?}",
+            path, settings, current_settings
+        );
+    }
+    (db, comments_db, previous_run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_utils::ScanOptions;
+    use std::env;
+    use std::panic;
+
+    #[test]
+    fn test_incremental_checks() {
+        let submission = |owner: &str| Submission {
+            owner: owner.to_string(),
+            files: vec![(
+                owner.to_string(),
+                "the mitochondria is the powerhouse of the cell".to_string(),
+            )],
+        };
+        let add = |db: &mut PlagiarismDatabase, submission: &Submission| {
+            db.add_untrusted_submission(&submission.owner, &submission.files, Language::Text)
+        };
+        let mut db = PlagiarismDatabase::new(3, 0, Metric::Equal, Vec::new());
+        let first = vec![submission("a"), submission("b")];
+        for submission in &first {
+            add(&mut db, submission);
+        }
+        let first_changes = find_changes(&HashMap::new(), &first);
+        assert_eq!(first_changes.changed.len(), 2);
+        assert_eq!(db.check_untrusted_plagiarism().len(), 1);
+
+        let path = env::temp_dir().join(format!("plagiarism-state-test-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        write_saved_file(path, "incremental state", STATE_FORMAT_VERSION, &db);
+        let mut db: PlagiarismDatabase =
+            read_saved_file(path, "incremental state", STATE_FORMAT_VERSION);
+        std::fs::remove_file(path).unwrap();
+        db.restore_after_load();
+
+        // Only the pairs including the new submission are checked
+        let second = vec![submission("a"), submission("b"), submission("c")];
+        let changes = find_changes(&first_changes.hashes, &second);
+        assert_eq!(changes.changed, HashSet::from(["c".to_string()]));
+        assert!(changes.removed.is_empty());
+        add(&mut db, &second[2]);
+        db.set_checked_owners(changes.changed.clone());
+        let results = db.check_untrusted_plagiarism();
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|result| result.owner_id1 == "c" || result.owner_id2 == "c"));
+
+        // A removed submission invalidates the results it was in
+        let changes = find_changes(&changes.hashes, &second[1..]);
+        assert_eq!(changes.removed, HashSet::from(["a".to_string()]));
+        let kept: Vec<bool> = results
+            .iter()
+            .map(|result| changes.is_unaffected(result))
+            .collect();
+        assert_eq!(kept.iter().filter(|kept| **kept).count(), 1);
+    }
+
+    #[test]
+    fn test_state_rejected_after_changes() {
+        let mut appsettings = AppSettings {
+            n: 3,
+            s: 0,
+            metric: Metric::Equal,
+            udir: "submissions".to_string(),
+            tdir: None,
+            idir: None,
+            adir: None,
+            index: None,
+            save_index: None,
+            incremental: None,
+            results_db: None,
+            ignore_metric: Metric::Equal,
+            ignore_s: 0,
+            exclude_stopwords: false,
+            boilerplate_frequency: None,
+            manifest: None,
+            section: None,
+            group_mode: GroupMode::Skip,
+            scan_options: ScanOptions::default(),
+            submission_dirs: false,
+            language: None,
+            cross_language: false,
+            split_comments: false,
+            dedup_sources: false,
+            comments_n: 3,
+            comments_s: 0,
+            output_cli: false,
+            output_html: false,
+            open_html_after: false,
+            quiet: true,
+            fail_threshold: 0.0,
+            effective_config: None,
+        };
+        let dir = env::temp_dir().join(format!("plagiarism-groups-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = |contents: &str| {
+            let path = dir.join("manifest.csv");
+            std::fs::write(&path, contents).unwrap();
+            Manifest::from_file(path.to_str().unwrap())
+        };
+        let teams = manifest("file,student_id,group\na,A01,team1\nb,A02,team1\n");
+        let path = dir.join("state");
+        let path = path.to_str().unwrap();
+        let db = PlagiarismDatabase::new(3, 0, Metric::Equal, Vec::new());
+        save_state(
+            path,
+            &appsettings,
+            &teams,
+            &db,
+            &None,
+            &HashMap::new(),
+            [&[], &[], &[]],
+        );
+        load_state(path, &appsettings, &teams);
+
+        // b moving to another group changes which pairs are skipped
+        let new_teams = manifest("file,student_id,group\na,A01,team1\nb,A02,team2\n");
+        assert!(panic::catch_unwind(|| load_state(path, &appsettings, &new_teams)).is_err());
+        // Different boilerplate would have ignored different fragments
+        appsettings.boilerplate_frequency = Some(0.5);
+        assert!(panic::catch_unwind(|| load_state(path, &appsettings, &teams)).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
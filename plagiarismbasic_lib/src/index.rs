@@ -0,0 +1,135 @@
+use crate::plagiarism_database::{Channel, IndexedTexts, PlagiarismDatabase};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+/// Version of the index file format. Must be increased whenever anything
+///     saved in the index changes shape, so that old indexes are rejected
+const INDEX_FORMAT_VERSION: u32 = 2;
+
+/// Writes a value to a gzipped file, preceded by the version of its format.
+///     The kind of file (e.g. "index") is used in error messages.
+///     Panics if the file cannot be written.
+pub fn write_saved_file<T: Serialize>(path: &str, kind: &str, version: u32, value: &T) {
+    let file = File::create(path)
+        .unwrap_or_else(|err| panic!("Cannot create {} {}: {}", kind, path, err));
+    let mut writer = GzEncoder::new(BufWriter::new(file), Compression::default());
+    bincode::serialize_into(&mut writer, &version)
+        .and_then(|_| bincode::serialize_into(&mut writer, value))
+        .unwrap_or_else(|err| panic!("Cannot write {} {}: {}", kind, path, err));
+    writer
+        .finish()
+        .unwrap_or_else(|err| panic!("Cannot write {} {}: {}", kind, path, err));
+}
+
+/// Reads a value written by write_saved_file. Panics if the file cannot be read
+///     or was saved with another version of its format.
+pub fn read_saved_file<T: DeserializeOwned>(path: &str, kind: &str, version: u32) -> T {
+    let file =
+        File::open(path).unwrap_or_else(|err| panic!("Cannot open {} {}: {}", kind, path, err));
+    let mut reader = GzDecoder::new(BufReader::new(file));
+    let saved_version: u32 = bincode::deserialize_from(&mut reader)
+        .unwrap_or_else(|err| panic!("Cannot read {} {}: {}", kind, path, err));
+    if saved_version != version {
+        panic!(
+            "File {} is an {} with format version {}, but this version of the program reads \
+             version {}. Please create it again.",
+            path, kind, saved_version, version
+        );
+    }
+    bincode::deserialize_from(&mut reader)
+        .unwrap_or_else(|err| panic!("Cannot read {} {}: {}", kind, path, err))
+}
+
+/// Saves the trusted and archived texts of the databases (one per channel) to a
+///     gzipped index file. Panics if the file cannot be written.
+pub fn save_index(path: &str, db: &PlagiarismDatabase, comments_db: &Option<PlagiarismDatabase>) {
+    let mut indexed: Vec<IndexedTexts> = vec![db.get_indexed_texts()];
+    if let Some(comments_db) = comments_db {
+        indexed.push(comments_db.get_indexed_texts());
+    }
+    write_saved_file(path, "index", INDEX_FORMAT_VERSION, &indexed);
+}
+
+/// Adds the texts saved in an index file to the databases as trusted and archived texts.
+///     Panics if the index was built with different fragment sizes or
+///     normalization, or with comments split out when they aren't now (or vice versa).
+///     Returns the number of texts loaded.
+pub fn load_index(
+    path: &str,
+    db: &mut PlagiarismDatabase,
+    comments_db: &mut Option<PlagiarismDatabase>,
+) -> usize {
+    let indexed: Vec<IndexedTexts> = read_saved_file(path, "index", INDEX_FORMAT_VERSION);
+    let has_comments = indexed
+        .iter()
+        .any(|texts| texts.channel == Channel::Comments);
+    if has_comments != comments_db.is_some() {
+        panic!(
+            "Index {} was built {} comments being compared separately, so it cannot be used {} them",
+            path,
+            if has_comments { "with" } else { "without" },
+            if has_comments { "without" } else { "with" }
+        );
+    }
+    let mut loaded = 0;
+    for texts in indexed {
+        loaded += texts.text_count();
+        match (texts.channel, comments_db.as_mut()) {
+            (Channel::Comments, Some(comments_db)) => comments_db.add_indexed_texts(texts),
+            _ => db.add_indexed_texts(texts),
+        }
+    }
+    loaded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_utils::Language;
+    use crate::Metric;
+    use std::env;
+
+    #[test]
+    fn test_save_and_load_index() {
+        let path =
+            env::temp_dir().join(format!("plagiarism-index-test-{}.idx", std::process::id()));
+        let path = path.to_str().unwrap();
+        let files = vec![(
+            "book.txt".to_string(),
+            "the quick brown fox jumps over the lazy dog".to_string(),
+        )];
+        let mut db = PlagiarismDatabase::new(3, 0, Metric::Equal, Vec::new());
+        db.add_trusted_submission("book.txt", &files, Language::Text);
+        save_index(path, &db, &None);
+
+        // The ignored text of the new run applies to the loaded texts
+        let mut db =
+            PlagiarismDatabase::new(3, 0, Metric::Equal, vec!["the quick brown fox".to_string()]);
+        assert_eq!(load_index(path, &mut db, &mut None), 1);
+        let files = vec![(
+            "alice.txt".to_string(),
+            "the quick brown fox jumps over the lazy cat".to_string(),
+        )];
+        db.add_untrusted_submission("alice.txt", &files, Language::Text);
+        let results = db.check_trusted_plagiarism();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].owner_id1, "book.txt");
+        assert_eq!(results[0].matching_fragments.len(), 4);
+        assert_eq!(
+            db.get_all_cleantext()["book.txt"].ignored_locations,
+            vec![(0, 3)]
+        );
+
+        let result = std::panic::catch_unwind(|| {
+            let mut db = PlagiarismDatabase::new(4, 0, Metric::Equal, Vec::new());
+            load_index(path, &mut db, &mut None);
+        });
+        std::fs::remove_file(path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -1,15 +1,45 @@
+mod archive_utils;
+mod attribution;
+mod code_utils;
+mod compare_view;
+mod document_utils;
+mod encoding_utils;
 mod file_utils;
+mod incremental;
+mod index;
+mod manifest;
 mod plagiarism_database;
+mod progress;
 mod result_output_html;
 mod result_printer;
+mod results_store;
 mod string_compare;
 mod text_utils;
 
-use file_utils::get_file_contents_from_dir;
-use plagiarism_database::{PlagiarismDatabase, PlagiarismResult};
+use attribution::attribute_sources;
+use code_utils::split_comments;
+use file_utils::{
+    detect_language, detect_submission_language, get_archived_submissions, get_file_contents,
+    get_single_file, get_submissions, SkippedFile, Submission,
+};
+use indicatif::ProgressBar;
+use manifest::Manifest;
+use plagiarism_database::{
+    Channel, CleanText, CommonFragment, PlagiarismDatabase, PlagiarismResult,
+};
+use progress::{Progress, Statistics};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+pub use compare_view::CompareView;
+pub use file_utils::{Language, ScanOptions, SymlinkPolicy};
+pub use manifest::GroupMode;
+pub use results_store::{run_query, Query};
 
 /// Indicates which metric is being used for plagiarism comparison
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
 pub enum Metric {
     /// Check for equality between strings
     Equal,
@@ -26,9 +56,108 @@ pub struct AppSettings {
     pub udir: String,
     pub tdir: Option<String>,
     pub idir: Option<String>,
+    /// Directory of submissions from past terms, with one subdirectory per term
+    pub adir: Option<String>,
+    /// Index file of previously read trusted and archived texts to check against as well
+    pub index: Option<String>,
+    /// Save the trusted and archived texts (including any from the index) to this index file
+    pub save_index: Option<String>,
+    /// State file of an incremental run, which is created on the first run and
+    ///     then lets later runs check only new or changed untrusted submissions
+    pub incremental: Option<String>,
+    /// SQLite database to store the run, its settings and all of its results in
+    pub results_db: Option<String>,
+    /// Metric used to match text against the ignored texts
+    pub ignore_metric: Metric,
+    /// Similarity (s) used to match text against the ignored texts
+    pub ignore_s: usize,
+    /// Leave stopwords out of the adjusted plagiarism percentages
+    pub exclude_stopwords: bool,
+    /// Ignore fragments found in more than this fraction (0 to 1) of untrusted submissions
+    pub boilerplate_frequency: Option<f32>,
+    /// CSV or JSON file with the student ID, name, group, section and timestamp
+    ///     of each untrusted submission
+    pub manifest: Option<String>,
+    /// Only report results involving a submission from this section of the manifest
+    pub section: Option<String>,
+    /// How submissions from members of the same group in the manifest are compared
+    pub group_mode: GroupMode,
+    /// Which files to read from the untrusted, trusted and ignore directories
+    pub scan_options: ScanOptions,
+    /// Treat each top-level subdirectory as one submission instead of each file
+    pub submission_dirs: bool,
+    /// Language to assume for every file instead of detecting it per file
+    pub language: Option<Language>,
+    /// Compare files even if they are written in different languages
+    pub cross_language: bool,
+    /// Compare comments and string literals in code separately from the code itself
+    pub split_comments: bool,
+    /// Attribute passages matching several overlapping trusted sources to just the best one
+    pub dedup_sources: bool,
+    /// Sensitivity (n) used for the comments channel
+    pub comments_n: usize,
+    /// Similarity (s) used for the comments channel
+    pub comments_s: usize,
     pub output_cli: bool,
     pub output_html: bool,
     pub open_html_after: bool,
+    /// Only print a one-line summary of the findings instead of the results
+    pub quiet: bool,
+    /// Results where at least this percentage of either text is matching text count
+    ///     as findings
+    pub fail_threshold: f32,
+    /// Settings the run was started with (e.g. as written in a config file) and where
+    ///     they came from, to show at the top of reports
+    pub effective_config: Option<String>,
+}
+
+/// Numbers of reported results that count as findings, i.e. where at least
+///     appsettings.fail_threshold percent of either text is matching text
+#[derive(Default, PartialEq, Debug)]
+pub struct Findings {
+    pub untrusted: usize,
+    pub trusted: usize,
+    pub archive: usize,
+    /// Highest percentage of a text that is matching text, over all reported results
+    pub highest_percent: f32,
+}
+
+impl Findings {
+    /// Counts the results that are findings, and the highest percentage over all of them
+    fn count(
+        untrusted: &[PlagiarismResult],
+        trusted: &[PlagiarismResult],
+        archive: &[PlagiarismResult],
+        threshold: f32,
+    ) -> Findings {
+        let percent = |result: &PlagiarismResult| {
+            result
+                .coverage1
+                .raw_percent
+                .max(result.coverage2.raw_percent)
+        };
+        let count = |results: &[PlagiarismResult]| {
+            results
+                .iter()
+                .filter(|result| percent(result) >= threshold)
+                .count()
+        };
+        Findings {
+            untrusted: count(untrusted),
+            trusted: count(trusted),
+            archive: count(archive),
+            highest_percent: untrusted
+                .iter()
+                .chain(trusted)
+                .chain(archive)
+                .map(percent)
+                .fold(0.0, f32::max),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.untrusted + self.trusted + self.archive
+    }
 }
 
 /// Reads settings from CLI input.
@@ -37,57 +166,628 @@ pub struct AppSettings {
 /// Runs the plagiarism algorithm with settings from CLI
 /// Prints results on CLI
 /// Renders results as HTML and opens it automatically using xdg-open if possible
-pub fn run_plagiarism_checks(appsettings: &AppSettings) {
+/// Returns the findings among the reported results
+pub fn run_plagiarism_checks(appsettings: &AppSettings) -> Findings {
+    // Show how far along each phase is, unless the output is going somewhere else
+    let mut progress = Progress::new(!appsettings.quiet && io::stdout().is_terminal());
+    progress.start_phase("Reading files", None);
+
     // Read all file contents in both specified directories
-    // Fail with panic if any file is not UTF8, or any other error
-    let untrusted_contents = get_file_contents_from_dir(&appsettings.udir);
+    // Files that can't be decoded as text are skipped and reported,
+    // fail with panic on any other error
+    let (mut untrusted_submissions, mut skipped_files) = get_submissions(
+        &appsettings.udir,
+        &appsettings.scan_options,
+        appsettings.submission_dirs,
+    );
+
+    let manifest = match &appsettings.manifest {
+        Some(path) => Manifest::from_file(path),
+        None => Manifest::default(),
+    };
+
+    // Carry on from the last incremental run, if there was one. Its trusted, archived
+    // and ignored texts are reused, so those directories aren't read again
+    let previous = appsettings
+        .incremental
+        .as_ref()
+        .filter(|path| Path::new(path).exists())
+        .map(|path| incremental::load_state(path, appsettings, &manifest));
+
+    let (trusted_submissions, archived_submissions) = match &previous {
+        None => read_sources(appsettings, &mut skipped_files),
+        Some(_) => (Vec::new(), Vec::new()),
+    };
+    // Members of merged groups are only checked as part of their group
+    let untrusted_owners: HashSet<String> = untrusted_submissions
+        .iter()
+        .map(|submission| submission.owner.clone())
+        .collect();
+    if appsettings.group_mode == GroupMode::Merge {
+        untrusted_submissions = manifest.merge_group_submissions(untrusted_submissions);
+    }
 
     // Try to add ignore-text if specified. This is required early for optimization.
     let mut ignored_texts: Vec<String> = Vec::new();
-    if let Some(idir) = &appsettings.idir {
-        let ignore_contents = get_file_contents_from_dir(&idir);
+    if let (Some(idir), None) = (&appsettings.idir, &previous) {
+        let (ignore_contents, mut skipped) = get_file_contents(idir, &appsettings.scan_options);
+        skipped_files.append(&mut skipped);
         for (_, val) in ignore_contents {
             ignored_texts.push(val);
         }
     }
 
-    // Add text to the DB
-    let mut db = PlagiarismDatabase::new(
-        appsettings.n,
-        appsettings.s,
-        appsettings.metric,
-        ignored_texts,
+    let files: usize = untrusted_submissions
+        .iter()
+        .chain(&trusted_submissions)
+        .chain(
+            archived_submissions
+                .iter()
+                .map(|(_, submission)| submission),
+        )
+        .map(|submission| submission.files.len())
+        .sum();
+    let bar = progress.start_phase(
+        "Fragmenting texts",
+        Some(
+            (untrusted_submissions.len() + trusted_submissions.len() + archived_submissions.len())
+                as u64,
+        ),
     );
 
-    for (id, val) in untrusted_contents {
-        db.add_untrusted_text(&id, &val);
+    // Add text to the DB. Comments in code go to their own DB if requested,
+    // so that they can be compared with a different n and s
+    let (mut db, mut comments_db, previous_run) = match previous {
+        Some((db, comments_db, previous_run)) => (db, comments_db, Some(previous_run)),
+        None => {
+            let (db, comments_db) = new_databases(appsettings, ignored_texts);
+            (db, comments_db, None)
+        }
+    };
+    db.set_groups(manifest.groups(), appsettings.group_mode == GroupMode::Skip);
+    if let Some(comments_db) = &mut comments_db {
+        comments_db.set_groups(manifest.groups(), appsettings.group_mode == GroupMode::Skip);
+    }
+
+    // On incremental runs, only submissions that are new or have changed since the
+    // last run are added again, and only pairs including them are checked
+    let changes = appsettings.incremental.as_ref().map(|_| {
+        let previous_hashes = previous_run
+            .as_ref()
+            .map(|run| run.submission_hashes.clone())
+            .unwrap_or_default();
+        incremental::find_changes(&previous_hashes, &untrusted_submissions)
+    });
+    if let Some(changes) = &changes {
+        for owner in changes.changed.iter().chain(&changes.removed) {
+            db.remove_untrusted_submission(owner);
+            if let Some(comments_db) = &mut comments_db {
+                comments_db.remove_untrusted_submission(owner);
+            }
+        }
+        db.set_checked_owners(changes.changed.clone());
+        if let Some(comments_db) = &mut comments_db {
+            comments_db.set_checked_owners(changes.changed.clone());
+        }
     }
 
-    // Try to add trusted text if specified
-    if let Some(tdir) = &appsettings.tdir {
-        let trusted_contents = get_file_contents_from_dir(&tdir);
-        for (id, val) in trusted_contents {
-            db.add_trusted_text(&id, &val);
+    for submission in &untrusted_submissions {
+        bar.inc(1);
+        if changes
+            .as_ref()
+            .is_some_and(|changes| !changes.changed.contains(&submission.owner))
+        {
+            continue;
         }
+        add_submission(
+            &mut db,
+            &mut comments_db,
+            submission,
+            appsettings.language,
+            SubmissionKind::Untrusted,
+        );
+    }
+
+    // Texts from an index stand in for trusted and archived texts that were read on a
+    // previous run. Texts read now replace any indexed texts with the same owner ID
+    if let (Some(index), None) = (&appsettings.index, &previous_run) {
+        index::load_index(index, &mut db, &mut comments_db);
+    }
+
+    // Add trusted text and submissions from past terms if specified
+    add_sources(
+        &mut db,
+        &mut comments_db,
+        appsettings,
+        &trusted_submissions,
+        &archived_submissions,
+        &bar,
+    );
+
+    if let Some(index) = &appsettings.save_index {
+        index::save_index(index, &db, &comments_db);
+    }
+
+    // Trusted and archived texts may have been kept from the last run or come from
+    // an index instead of being read now, so look for them in the databases
+    for owner in manifest.owners() {
+        if !untrusted_owners.contains(owner)
+            && !db.has_owner(owner)
+            && !comments_db
+                .as_ref()
+                .is_some_and(|comments_db| comments_db.has_owner(owner))
+        {
+            bar.suspend(|| eprintln!("Manifest entry {} does not match any submission", owner));
+        }
+    }
+
+    // Ignore boilerplate shared by most submissions, e.g. the assignment prompt
+    let mut common_fragments: Vec<CommonFragment> = Vec::new();
+    if let Some(max_frequency) = appsettings.boilerplate_frequency {
+        common_fragments.append(&mut db.ignore_common_fragments(max_frequency));
+        if let Some(comments_db) = &mut comments_db {
+            common_fragments.append(&mut comments_db.ignore_common_fragments(max_frequency));
+        }
+    }
+
+    let (mut words, mut fragments) = db.count_words_and_fragments();
+    if let Some(comments_db) = &comments_db {
+        let (comments_words, comments_fragments) = comments_db.count_words_and_fragments();
+        words += comments_words;
+        fragments += comments_fragments;
+    }
+    progress.finish_phase();
+
+    // Don't let skipped files go unnoticed even if results aren't printed
+    for skipped in &skipped_files {
+        eprintln!("Skipped {}: {}", skipped.path, skipped.reason);
+    }
+
+    let pairs = db.count_pairs(appsettings.dedup_sources)
+        + comments_db.as_ref().map_or(0, |comments_db| {
+            comments_db.count_pairs(appsettings.dedup_sources)
+        });
+    let bar = progress.start_phase("Comparing texts", Some(pairs));
+    db.set_progress(bar.clone());
+    if let Some(comments_db) = &mut comments_db {
+        comments_db.set_progress(bar);
     }
 
     // Run both inter-source plagiarism and external-source-based plagiarism checks
+    // If asked to, trusted sources are first compared to each other so that passages
+    // they share are only attributed to the best source (earliest by manifest timestamp
+    // if they cover as much of the text as each other)
+    let source_dates = manifest.timestamps();
+    let check_trusted = |db: &PlagiarismDatabase| {
+        let results = db.check_trusted_plagiarism();
+        if appsettings.dedup_sources {
+            let overlaps = db.check_trusted_overlap();
+            (
+                db.attribute_to_best_sources(results, &overlaps, &source_dates),
+                overlaps,
+            )
+        } else {
+            (results, Vec::new())
+        }
+    };
     let mut ut_result: Vec<PlagiarismResult> = db.check_untrusted_plagiarism();
-    let mut t_result: Vec<PlagiarismResult> = db.check_trusted_plagiarism();
+    let (mut t_result, mut overlap_result) = check_trusted(&db);
+    let mut a_result: Vec<PlagiarismResult> = db.check_archive_plagiarism();
+    let mut all_cleantext = HashMap::new();
+    all_cleantext.insert(Channel::Content, db.get_all_cleantext());
+    if let Some(comments_db) = &comments_db {
+        ut_result.append(&mut comments_db.check_untrusted_plagiarism());
+        let (mut comments_t_result, mut comments_overlap_result) = check_trusted(comments_db);
+        t_result.append(&mut comments_t_result);
+        overlap_result.append(&mut comments_overlap_result);
+        a_result.append(&mut comments_db.check_archive_plagiarism());
+        all_cleantext.insert(Channel::Comments, comments_db.get_all_cleantext());
+    }
+    progress.start_phase_without_bar("Saving and reporting");
+
+    // Carry over the results of the last incremental run that are still valid,
+    // then save everything for the next run to carry on from
+    if let (Some(path), Some(changes)) = (&appsettings.incremental, &changes) {
+        if let Some(previous_run) = previous_run {
+            let is_unaffected = |result: &PlagiarismResult| changes.is_unaffected(result);
+            ut_result.extend(previous_run.untrusted_results.into_iter().filter(is_unaffected));
+            t_result.extend(previous_run.trusted_results.into_iter().filter(is_unaffected));
+            a_result.extend(previous_run.archive_results.into_iter().filter(is_unaffected));
+        }
+        incremental::save_state(
+            path,
+            appsettings,
+            &manifest,
+            &db,
+            &comments_db,
+            &changes.hashes,
+            [&ut_result, &t_result, &a_result],
+        );
+    }
+
+    // Store the whole run before any results are filtered out of the report
+    if let Some(path) = &appsettings.results_db {
+        let run = results_store::save_run(
+            path,
+            appsettings,
+            &manifest,
+            &db.get_text_summaries(),
+            ut_result.iter().chain(&t_result).chain(&a_result),
+        );
+        if !appsettings.quiet {
+            println!("Saved results as run {} in {}", run, path);
+        }
+    }
 
-    // Print them separately on the CLI
-    if appsettings.output_cli {
-        result_printer::print_results_ut(&mut ut_result);
-        result_printer::print_results_t(&mut t_result);
+    let results: Vec<&PlagiarismResult> =
+        ut_result.iter().chain(&t_result).chain(&a_result).collect();
+    let (results, matching_fragments) = (
+        results.len(),
+        results
+            .iter()
+            .map(|result| result.matching_fragments.len())
+            .sum(),
+    );
+
+    let findings = output_report(
+        appsettings,
+        Results {
+            untrusted: ut_result,
+            trusted: t_result,
+            archive: a_result,
+            overlap: overlap_result,
+        },
+        all_cleantext,
+        &skipped_files,
+        &common_fragments,
+        &manifest,
+        appsettings.exclude_stopwords,
+    );
+    if !appsettings.quiet {
+        result_printer::print_statistics(&Statistics {
+            files,
+            words,
+            fragments,
+            pairs,
+            results,
+            matching_fragments,
+            timings: progress.timings(),
+        });
+    }
+    findings
+}
+
+/// Reads the trusted and archived texts and saves them (along with any texts already in
+///     the index given by appsettings.index) to the index file appsettings.save_index,
+///     so that later checks can use them without reading them again
+pub fn build_index(appsettings: &AppSettings) {
+    let path = appsettings
+        .save_index
+        .as_ref()
+        .expect("No index file to save to was given!");
+    let mut skipped_files: Vec<SkippedFile> = Vec::new();
+    let (trusted_submissions, archived_submissions) =
+        read_sources(appsettings, &mut skipped_files);
+    let (mut db, mut comments_db) = new_databases(appsettings, Vec::new());
+    if let Some(index) = &appsettings.index {
+        index::load_index(index, &mut db, &mut comments_db);
+    }
+    add_sources(
+        &mut db,
+        &mut comments_db,
+        appsettings,
+        &trusted_submissions,
+        &archived_submissions,
+        &ProgressBar::hidden(),
+    );
+    for skipped in &skipped_files {
+        eprintln!("Skipped {}: {}", skipped.path, skipped.reason);
+    }
+    index::save_index(path, &db, &comments_db);
+    println!(
+        "Saved {} texts to index {}",
+        db.get_text_summaries().len(),
+        path
+    );
+}
+
+/// Reports the results saved in the state file of an incremental run again,
+///     e.g. as HTML when they were only printed on the CLI. The manifest, section and
+///     output settings are taken from appsettings; everything else was saved in the state.
+///     Returns the findings among the reported results
+pub fn report_saved_results(state_path: &str, appsettings: &AppSettings) -> Findings {
+    let (settings, db, comments_db, previous_run) = incremental::read_state(state_path);
+    let manifest = match &appsettings.manifest {
+        Some(path) => Manifest::from_file(path),
+        None => Manifest::default(),
+    };
+    let mut all_cleantext = HashMap::new();
+    all_cleantext.insert(Channel::Content, db.get_all_cleantext());
+    if let Some(comments_db) = &comments_db {
+        all_cleantext.insert(Channel::Comments, comments_db.get_all_cleantext());
+    }
+    output_report(
+        appsettings,
+        Results {
+            untrusted: previous_run.untrusted_results,
+            trusted: previous_run.trusted_results,
+            archive: previous_run.archive_results,
+            overlap: Vec::new(),
+        },
+        all_cleantext,
+        &[],
+        &[],
+        &manifest,
+        settings.exclude_stopwords(),
+    )
+}
+
+/// Compares two files with each other and prints both of them on the CLI with
+///     the matching text coloured in, wrapped to the given width. Colours are left
+///     out if the output isn't a terminal. Returns whether any matching text was found
+pub fn compare_files(
+    path1: &str,
+    path2: &str,
+    appsettings: &AppSettings,
+    view: CompareView,
+    width: usize,
+) -> bool {
+    if !io::stdout().is_terminal() {
+        colored::control::set_override(false);
+    }
+    // The files get owners of their own, so that a file can be compared with itself
+    let owners = ["1", "2"];
+    let submissions = [
+        get_single_file(path1, owners[0]),
+        get_single_file(path2, owners[1]),
+    ];
+    let (mut db, mut comments_db) = new_databases(appsettings, Vec::new());
+    for submission in &submissions {
+        add_submission(
+            &mut db,
+            &mut comments_db,
+            submission,
+            appsettings.language,
+            SubmissionKind::Untrusted,
+        );
+    }
+    let mut found = false;
+    for db in std::iter::once(&db).chain(&comments_db) {
+        let texts = db.get_all_cleantext();
+        for result in db.check_untrusted_plagiarism() {
+            compare_view::print_comparison(&result, &texts, owners, [path1, path2], view, width);
+            found = true;
+        }
+    }
+    if !found {
+        println!("Similarity: 0%");
+        println!("No matching text found between {} and {}", path1, path2);
+    }
+    found
+}
+
+/// Results of the checks, by which texts were compared
+struct Results {
+    untrusted: Vec<PlagiarismResult>,
+    trusted: Vec<PlagiarismResult>,
+    archive: Vec<PlagiarismResult>,
+    /// Overlaps between trusted texts
+    overlap: Vec<PlagiarismResult>,
+}
+
+/// Filters the results to the requested section, then prints them on the CLI
+///     and/or renders them as HTML as requested. Returns the findings among them
+#[allow(clippy::too_many_arguments)]
+fn output_report(
+    appsettings: &AppSettings,
+    results: Results,
+    all_cleantext: HashMap<Channel, HashMap<String, CleanText>>,
+    skipped_files: &[SkippedFile],
+    common_fragments: &[CommonFragment],
+    manifest: &Manifest,
+    exclude_stopwords: bool,
+) -> Findings {
+    let Results {
+        untrusted: mut ut_result,
+        trusted: mut t_result,
+        archive: mut a_result,
+        overlap: mut overlap_result,
+    } = results;
+
+    // Only keep results for the requested section, if any
+    if let Some(section) = &appsettings.section {
+        let in_section = |result: &PlagiarismResult| {
+            manifest.in_section(&result.owner_id1, section)
+                || manifest.in_section(&result.owner_id2, section)
+        };
+        ut_result.retain(in_section);
+        t_result.retain(in_section);
+        a_result.retain(in_section);
+    }
+
+    let findings = Findings::count(&ut_result, &t_result, &a_result, appsettings.fail_threshold);
+
+    // Merge the trusted results for each untrusted text into one view of its sources
+    let attributions = attribute_sources(
+        &t_result,
+        &all_cleantext,
+        &manifest.timestamps(),
+        exclude_stopwords,
+    );
+
+    // Print them separately on the CLI, or just sum them up if asked to be quiet
+    if appsettings.quiet {
+        result_printer::print_summary(&findings, appsettings.fail_threshold);
+    } else if appsettings.output_cli {
+        if let Some(config) = &appsettings.effective_config {
+            result_printer::print_settings(config);
+        }
+        result_printer::print_common_fragments(common_fragments);
+        result_printer::print_results_ut(&mut ut_result, manifest);
+        result_printer::print_results_t(&mut t_result, manifest);
+        result_printer::print_results_archive(&mut a_result, manifest);
+        result_printer::print_results_overlap(&mut overlap_result, manifest);
+        result_printer::print_attributions(&attributions, manifest);
     }
 
     if appsettings.output_html {
         // Pass them together to the HTML output module
         ut_result.append(&mut t_result);
+        ut_result.append(&mut a_result);
         result_output_html::output_results(
             &mut ut_result,
-            db.get_all_cleantext(),
+            all_cleantext,
+            skipped_files,
+            common_fragments,
+            &overlap_result,
+            &attributions,
+            manifest,
+            appsettings.effective_config.as_deref(),
             appsettings.open_html_after,
         );
     }
+    findings
+}
+
+/// Creates the databases texts are added to, the second only if comments in
+///     code are compared separately
+fn new_databases(
+    appsettings: &AppSettings,
+    ignored_texts: Vec<String>,
+) -> (PlagiarismDatabase, Option<PlagiarismDatabase>) {
+    let mut db = PlagiarismDatabase::new(
+        appsettings.n,
+        appsettings.s,
+        appsettings.metric,
+        ignored_texts.clone(),
+    );
+    db.set_cross_language(appsettings.cross_language);
+    db.set_ignore_metric(appsettings.ignore_metric, appsettings.ignore_s);
+    db.set_exclude_stopwords(appsettings.exclude_stopwords);
+    let comments_db = if appsettings.split_comments {
+        let mut comments_db = PlagiarismDatabase::new(
+            appsettings.comments_n,
+            appsettings.comments_s,
+            appsettings.metric,
+            ignored_texts,
+        );
+        comments_db.set_cross_language(appsettings.cross_language);
+        comments_db.set_ignore_metric(appsettings.ignore_metric, appsettings.ignore_s);
+        comments_db.set_exclude_stopwords(appsettings.exclude_stopwords);
+        comments_db.set_channel(Channel::Comments);
+        Some(comments_db)
+    } else {
+        None
+    };
+    (db, comments_db)
+}
+
+/// Reads the trusted and archived submissions, if their directories are given
+fn read_sources(
+    appsettings: &AppSettings,
+    skipped_files: &mut Vec<SkippedFile>,
+) -> (Vec<Submission>, Vec<(String, Submission)>) {
+    let trusted_submissions: Vec<Submission> = match &appsettings.tdir {
+        Some(tdir) => {
+            let (trusted_submissions, mut skipped) =
+                get_submissions(tdir, &appsettings.scan_options, appsettings.submission_dirs);
+            skipped_files.append(&mut skipped);
+            trusted_submissions
+        }
+        None => Vec::new(),
+    };
+
+    let archived_submissions: Vec<(String, Submission)> = match &appsettings.adir {
+        Some(adir) => {
+            let (archived_submissions, mut skipped) = get_archived_submissions(
+                adir,
+                &appsettings.scan_options,
+                appsettings.submission_dirs,
+            );
+            skipped_files.append(&mut skipped);
+            archived_submissions
+        }
+        None => Vec::new(),
+    };
+    (trusted_submissions, archived_submissions)
+}
+
+/// Adds trusted submissions and submissions from past terms to the DB, ticking the
+///     progress bar once for each
+fn add_sources(
+    db: &mut PlagiarismDatabase,
+    comments_db: &mut Option<PlagiarismDatabase>,
+    appsettings: &AppSettings,
+    trusted_submissions: &[Submission],
+    archived_submissions: &[(String, Submission)],
+    progress: &ProgressBar,
+) {
+    for submission in trusted_submissions {
+        progress.inc(1);
+        add_submission(
+            db,
+            comments_db,
+            submission,
+            appsettings.language,
+            SubmissionKind::Trusted,
+        );
+    }
+    for (term, submission) in archived_submissions {
+        progress.inc(1);
+        add_submission(
+            db,
+            comments_db,
+            submission,
+            appsettings.language,
+            SubmissionKind::Archived(term),
+        );
+    }
+}
+
+/// Which set of texts a submission is added to
+#[derive(Copy, Clone)]
+enum SubmissionKind<'a> {
+    Untrusted,
+    Trusted,
+    /// A submission from a past term, with the name of the term
+    Archived(&'a str),
+}
+
+/// Adds a submission to the DB as untrusted, trusted or archived text.
+///     If there is a comments DB, comments in code files are split off into it.
+fn add_submission(
+    db: &mut PlagiarismDatabase,
+    comments_db: &mut Option<PlagiarismDatabase>,
+    submission: &Submission,
+    language_override: Option<Language>,
+    kind: SubmissionKind,
+) {
+    let language =
+        language_override.unwrap_or_else(|| detect_submission_language(&submission.files));
+    let mut content_files: Vec<(String, String)> = Vec::new();
+    let mut comment_files: Vec<(String, String)> = Vec::new();
+    for (file_name, text) in &submission.files {
+        let file_language =
+            language_override.unwrap_or_else(|| detect_language(file_name, text));
+        match (comments_db.is_some(), split_comments(text, file_language)) {
+            (true, Some((code, comments))) => {
+                content_files.push((file_name.clone(), code));
+                comment_files.push((file_name.clone(), comments));
+            }
+            _ => content_files.push((file_name.clone(), text.clone())),
+        }
+    }
+
+    let owner = &submission.owner;
+    let add = |db: &mut PlagiarismDatabase, files: &[(String, String)]| match kind {
+        SubmissionKind::Untrusted => db.add_untrusted_submission(owner, files, language),
+        SubmissionKind::Trusted => db.add_trusted_submission(owner, files, language),
+        SubmissionKind::Archived(term) => db.add_archived_submission(owner, term, files, language),
+    };
+    add(db, &content_files);
+    if let Some(comments_db) = comments_db {
+        if !comment_files.is_empty() {
+            add(comments_db, &comment_files);
+        }
+    }
 }
@@ -0,0 +1,318 @@
+use crate::file_utils::Submission;
+use crate::plagiarism_database::TextOwnerID;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+
+/// Who made a submission, as given in a manifest file
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct SubmissionInfo {
+    /// Owner ID of the submission: its file (or submission directory) name,
+    ///     relative to the untrusted (or trusted) directory
+    pub file: String,
+    pub student_id: String,
+    /// Display name of the student
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Group or team the student belongs to
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Class section or tutorial group
+    #[serde(default)]
+    pub section: Option<String>,
+    /// When the submission was made (or a trusted source was published),
+    ///     ideally in ISO 8601 format
+    ///     (e.g. 2024-03-01T13:45:00) so that timestamps sort correctly
+    #[serde(default)]
+    pub timestamp: Option<String>,
+}
+
+/// How submissions from members of the same group are compared
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum GroupMode {
+    /// Don't compare members of the same group to each other
+    Skip,
+    /// Compare members of the same group, but mark the results as such
+    Label,
+    /// Combine each group's submissions into one, so groups are compared as units
+    Merge,
+}
+
+/// Maps owner IDs to the metadata of their submissions
+#[derive(Default, Debug)]
+pub struct Manifest {
+    entries: HashMap<TextOwnerID, SubmissionInfo>,
+}
+
+impl Manifest {
+    /// Reads a manifest from a CSV file (with a header row) or a JSON file
+    ///     (an array of objects) with the fields of SubmissionInfo.
+    ///     Panics if the file cannot be read or parsed.
+    pub fn from_file(path: &str) -> Manifest {
+        let contents = fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("Cannot read manifest file {}: {}", path, err));
+        let infos = if path.to_lowercase().ends_with(".json") {
+            serde_json::from_str(&contents)
+                .unwrap_or_else(|err| panic!("Cannot parse manifest file {}: {}", path, err))
+        } else {
+            Manifest::parse_csv(path, &contents)
+        };
+        Manifest::from_infos(path, infos)
+    }
+
+    fn parse_csv(path: &str, contents: &str) -> Vec<SubmissionInfo> {
+        csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(contents.as_bytes())
+            .deserialize()
+            .enumerate()
+            .map(|(i, row)| {
+                row.unwrap_or_else(|err| {
+                    panic!(
+                        "Cannot parse row {} of manifest file {}: {}",
+                        i + 1,
+                        path,
+                        err
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn from_infos(path: &str, infos: Vec<SubmissionInfo>) -> Manifest {
+        let mut entries: HashMap<TextOwnerID, SubmissionInfo> = HashMap::new();
+        for info in infos {
+            if entries.contains_key(&info.file) {
+                panic!(
+                    "{} is listed more than once in manifest file {}",
+                    info.file, path
+                );
+            }
+            entries.insert(info.file.clone(), info);
+        }
+        Manifest { entries }
+    }
+
+    /// Gets the metadata for an owner, if the manifest lists them
+    pub fn get(&self, owner: &str) -> Option<&SubmissionInfo> {
+        self.entries.get(owner)
+    }
+
+    /// Owner IDs of every submission listed in the manifest
+    pub fn owners(&self) -> impl Iterator<Item = &TextOwnerID> {
+        self.entries.keys()
+    }
+
+    /// Mapping of owner ID to group for every owner that is in a group
+    pub fn groups(&self) -> HashMap<TextOwnerID, String> {
+        self.entries
+            .iter()
+            .filter_map(|(owner, info)| Some((owner.clone(), info.group.clone()?)))
+            .collect()
+    }
+
+    /// Mapping of owner ID to timestamp for every owner that has a timestamp
+    pub fn timestamps(&self) -> HashMap<TextOwnerID, String> {
+        self.entries
+            .iter()
+            .filter_map(|(owner, info)| Some((owner.clone(), info.timestamp.clone()?)))
+            .collect()
+    }
+
+    /// Everyone listed as a member of this group, sorted by owner ID
+    fn group_members(&self, group: &str) -> Vec<&SubmissionInfo> {
+        let mut members: Vec<&SubmissionInfo> = self
+            .entries
+            .values()
+            .filter(|info| info.group.as_deref() == Some(group))
+            .collect();
+        members.sort_by(|a, b| a.file.cmp(&b.file));
+        members
+    }
+
+    /// Names an owner for reports, e.g. "Alice Tan (A0123456, alice.txt)".
+    ///     Merged groups are named by their members, e.g. "team1 (group: Alice Tan, Bob Lim)".
+    ///     Owners that are not in the manifest are named by their ID only.
+    pub fn label(&self, owner: &str) -> String {
+        match self.get(owner) {
+            Some(SubmissionInfo {
+                name: Some(name),
+                student_id,
+                ..
+            }) => format!("{} ({}, {})", name, student_id, owner),
+            Some(info) => format!("{} ({})", info.student_id, owner),
+            None => {
+                let members: Vec<&str> = self
+                    .group_members(owner)
+                    .iter()
+                    .map(|info| info.name.as_deref().unwrap_or(&info.student_id))
+                    .collect();
+                if members.is_empty() {
+                    owner.to_string()
+                } else {
+                    format!("{} (group: {})", owner, members.join(", "))
+                }
+            }
+        }
+    }
+
+    /// Checks if an owner (or any member of a merged group) is listed as being
+    ///     in this section
+    pub fn in_section(&self, owner: &str, section: &str) -> bool {
+        let in_section = |info: &SubmissionInfo| info.section.as_deref() == Some(section);
+        match self.get(owner) {
+            Some(info) => in_section(info),
+            None => self.group_members(owner).into_iter().any(in_section),
+        }
+    }
+
+    /// Combines the submissions of each group's members into one submission
+    ///     named after the group. Each file is renamed to start with the
+    ///     member's owner ID so it can still be told apart in reports.
+    ///     Submissions that are not in a group are kept as they are.
+    ///     Panics if a group has the same name as a submission that is not in a group.
+    pub fn merge_group_submissions(&self, submissions: Vec<Submission>) -> Vec<Submission> {
+        let mut merged: Vec<Submission> = Vec::new();
+        let mut group_indices: HashMap<String, usize> = HashMap::new();
+        for submission in submissions {
+            let group = match self
+                .get(&submission.owner)
+                .and_then(|info| info.group.clone())
+            {
+                Some(group) => group,
+                None => {
+                    merged.push(submission);
+                    continue;
+                }
+            };
+            let owner = submission.owner;
+            let files = submission.files.into_iter().map(|(file_name, text)| {
+                if file_name == owner {
+                    (file_name, text)
+                } else {
+                    (format!("{}/{}", owner, file_name), text)
+                }
+            });
+            match group_indices.get(&group) {
+                Some(&i) => merged[i].files.extend(files),
+                None => {
+                    group_indices.insert(group.clone(), merged.len());
+                    merged.push(Submission {
+                        owner: group,
+                        files: files.collect(),
+                    });
+                }
+            }
+        }
+        // A group named like an ungrouped submission would replace it in the checks
+        let clash = merged.iter().enumerate().find(|(i, submission)| {
+            group_indices
+                .get(&submission.owner)
+                .is_some_and(|group_index| group_index != i)
+        });
+        if let Some((_, submission)) = clash {
+            panic!(
+                "Group {} in the manifest has the same name as a submission that is not \
+                 in a group! Please rename the group or the submission.",
+                submission.owner
+            );
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let infos = Manifest::parse_csv(
+            "manifest.csv",
+            "file,student_id,name,group,section,timestamp\n\
+             alice.txt, A01, Alice Tan, team1, T01, 2024-03-01T13:45:00\n\
+             bob.txt,A02,,,T02,\n",
+        );
+        let manifest = Manifest::from_infos("manifest.csv", infos);
+        assert_eq!(manifest.label("alice.txt"), "Alice Tan (A01, alice.txt)");
+        assert_eq!(manifest.label("bob.txt"), "A02 (bob.txt)");
+        assert_eq!(manifest.label("carol.txt"), "carol.txt");
+        assert_eq!(manifest.get("bob.txt").unwrap().group, None);
+        assert!(manifest.in_section("alice.txt", "T01"));
+        assert!(!manifest.in_section("bob.txt", "T01"));
+    }
+
+    #[test]
+    fn test_merge_groups() {
+        let infos = Manifest::parse_csv(
+            "manifest.csv",
+            "file,student_id,name,group,section\n\
+             alice.txt,A01,Alice Tan,team1,T01\n\
+             bob,A02,Bob Lim,team1,T02\n\
+             carol.txt,A03,,,T02\n",
+        );
+        let manifest = Manifest::from_infos("manifest.csv", infos);
+        let submission = |owner: &str, files: &[&str]| Submission {
+            owner: owner.to_string(),
+            files: files
+                .iter()
+                .map(|file| (file.to_string(), String::new()))
+                .collect(),
+        };
+        let merged = manifest.merge_group_submissions(vec![
+            submission("alice.txt", &["alice.txt"]),
+            submission("bob", &["a.txt", "b.txt"]),
+            submission("carol.txt", &["carol.txt"]),
+        ]);
+        let owners: Vec<(&str, Vec<&str>)> = merged
+            .iter()
+            .map(|s| {
+                (
+                    s.owner.as_str(),
+                    s.files.iter().map(|f| f.0.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            owners,
+            vec![
+                ("team1", vec!["alice.txt", "bob/a.txt", "bob/b.txt"]),
+                ("carol.txt", vec!["carol.txt"]),
+            ]
+        );
+        assert_eq!(manifest.label("team1"), "team1 (group: Alice Tan, Bob Lim)");
+        assert!(manifest.in_section("team1", "T02"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Group bob in the manifest has the same name")]
+    fn test_merge_group_named_like_submission() {
+        let infos = Manifest::parse_csv(
+            "manifest.csv",
+            "file,student_id,name,group,section\n\
+             alice,A01,,bob,\n\
+             carol,A03,,bob,\n",
+        );
+        let manifest = Manifest::from_infos("manifest.csv", infos);
+        let submission = |owner: &str| Submission {
+            owner: owner.to_string(),
+            files: vec![(owner.to_string(), String::new())],
+        };
+        manifest.merge_group_submissions(vec![
+            submission("alice"),
+            submission("bob"),
+            submission("carol"),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let infos: Vec<SubmissionInfo> =
+            serde_json::from_str(r#"[{"file": "alice", "student_id": "A01", "group": "team1"}]"#)
+                .unwrap();
+        let manifest = Manifest::from_infos("manifest.json", infos);
+        let alice = manifest.get("alice").unwrap();
+        assert_eq!(alice.group.as_deref(), Some("team1"));
+        assert_eq!(alice.name, None);
+    }
+}
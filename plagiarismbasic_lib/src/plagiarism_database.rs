@@ -1,43 +1,323 @@
+use crate::file_utils::Language;
+use crate::document_utils::PAGE_SEPARATOR;
 use crate::string_compare::is_plagiarised;
-use crate::text_utils::{clean_text, extract_clean_word_ngrams};
+use crate::text_utils::{
+    clean_text, extract_clean_word_ngrams, is_stopword, NORMALIZATION_VERSION,
+};
 use crate::Metric;
-use serde::Serialize;
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
-use rayon::prelude::*;
 
 pub type TextOwnerID = String;
 /// (start index (inclusive), end index (exclusive))
 pub type FragmentLocation = (usize, usize);
 
+/// Which part of a text is being compared
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Natural language text, or code with comments and string literals removed
+    Content,
+    /// Comments and string literals extracted from code
+    Comments,
+}
+
+impl Channel {
+    /// Human-readable name of the channel for reports
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Content => "content",
+            Channel::Comments => "comments",
+        }
+    }
+}
+
+/// Which set of texts a text belongs to
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum TextCategory {
+    Untrusted,
+    Trusted,
+    /// Submissions from past terms
+    Archived,
+}
+
+// Only used when storing results in an SQLite database
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+impl TextCategory {
+    /// Human-readable name of the category for reports
+    pub fn name(self) -> &'static str {
+        match self {
+            TextCategory::Untrusted => "untrusted",
+            TextCategory::Trusted => "trusted",
+            TextCategory::Archived => "archived",
+        }
+    }
+}
+
 /// Report for plagiarism between two owners
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct PlagiarismResult {
     pub owner_id1: TextOwnerID,
     pub owner_id2: TextOwnerID,
+    /// Which part of the two texts was compared
+    pub channel: Channel,
     /// Each element is one matching tuple of text, one from each source
     pub matching_fragments: Vec<(String, String)>,
     /// Each element is the locations of one of the matching texts,
     ///     corresponding to each element of matching_fragments
     pub matching_fragments_locations: Vec<(Vec<FragmentLocation>, Vec<FragmentLocation>)>,
+    /// Each element is the names of the files that one of the matching texts
+    ///     was first found in, corresponding to each element of matching_fragments
+    pub matching_fragments_files: Vec<(String, String)>,
+    /// Each element is the page numbers that one of the matching texts was first
+    ///     found on (None for files without pages), corresponding to each element
+    ///     of matching_fragments
+    pub matching_fragments_pages: Vec<(Option<usize>, Option<usize>)>,
+    /// How much of each text is covered by the matching texts
+    pub coverage1: Coverage,
+    pub coverage2: Coverage,
     pub trusted_owner1: bool,  // Is the first owner a trusted source?
     pub equal_fragments: bool, // Can we ignore one element of the tuple?
+    pub same_group: bool,      // Are both owners members of the same group?
+    /// Term the first owner's submission was made in, if it is archived from a past term
+    pub term1: Option<String>,
+}
+
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+impl PlagiarismResult {
+    /// Which set of texts the first owner's text belongs to. The second owner's
+    ///     text is always untrusted, except for overlaps between trusted texts.
+    pub fn category1(&self) -> TextCategory {
+        match (self.trusted_owner1, &self.term1) {
+            (true, _) => TextCategory::Trusted,
+            (false, Some(_)) => TextCategory::Archived,
+            (false, None) => TextCategory::Untrusted,
+        }
+    }
+
+    /// Orders trusted results for the same untrusted text from the best source to
+    ///     the worst: the one that covers the most of the untrusted text, then the
+    ///     one with the earliest date (e.g. of publication) if given, then the
+    ///     first by ID
+    pub fn cmp_sources(
+        &self,
+        other: &PlagiarismResult,
+        source_dates: &HashMap<TextOwnerID, String>,
+    ) -> Ordering {
+        other
+            .coverage2
+            .raw_percent
+            .partial_cmp(&self.coverage2.raw_percent)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| {
+                match (
+                    source_dates.get(&self.owner_id1),
+                    source_dates.get(&other.owner_id1),
+                ) {
+                    (Some(date1), Some(date2)) => date1.cmp(date2),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                }
+            })
+            .then_with(|| self.owner_id1.cmp(&other.owner_id1))
+    }
+}
+
+/// How much of a text was found to be plagiarized
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub struct Coverage {
+    /// Percentage of all words in the text
+    pub raw_percent: f32,
+    /// Percentage of the words that were checked, i.e. excluding ignored words
+    ///     (and stopwords, if they are excluded)
+    pub adjusted_percent: f32,
+}
+
+/// A fragment found in so many untrusted texts that it was ignored as boilerplate
+#[derive(Serialize, Clone, Debug)]
+pub struct CommonFragment {
+    pub fragment: String,
+    /// How many untrusted texts the fragment was found in
+    pub texts: usize,
+    /// Which part of the texts the fragment was found in
+    pub channel: Channel,
+}
+
+/// Fragments shared by fewer untrusted texts than this are never treated as
+///     boilerplate, since a fragment shared by two texts is what we're looking for
+const MIN_COMMON_FRAGMENT_TEXTS: usize = 3;
+
+/// The cleaned words of a text, along with where each file (and page) of the text starts
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CleanText {
+    pub words: Vec<String>,
+    /// (file name, index of the first word of the file) for each file, in order
+    pub file_starts: Vec<(String, usize)>,
+    /// (index of the first word of the page, page number) for each page of files
+    ///     that have more than one page (e.g. PDFs), in order
+    pub page_starts: Vec<(usize, usize)>,
+    /// Sorted, non-overlapping ranges of words (both ends inclusive) that
+    ///     matched ignored text and so are excluded from checks
+    pub ignored_locations: Vec<FragmentLocation>,
+}
+
+impl CleanText {
+    /// Gets the name of the file that the word at this index came from
+    pub fn file_at(&self, word_index: usize) -> &str {
+        self.file_starts
+            .iter()
+            .rev()
+            .find(|(_, start)| *start <= word_index)
+            .or_else(|| self.file_starts.first())
+            .map(|(file_name, _)| file_name.as_str())
+            .unwrap_or("")
+    }
+
+    /// Adds ranges of ignored words, merging them with any overlapping
+    ///     or adjacent ranges that were already ignored
+    fn add_ignored_locations(&mut self, locations: impl IntoIterator<Item = FragmentLocation>) {
+        let mut all: Vec<FragmentLocation> = self.ignored_locations.drain(..).collect();
+        all.extend(locations);
+        all.sort_unstable();
+        for (start, end) in all {
+            match self.ignored_locations.last_mut() {
+                Some((_, last_end)) if start <= *last_end + 1 => {
+                    *last_end = (*last_end).max(end);
+                }
+                _ => self.ignored_locations.push((start, end)),
+            }
+        }
+    }
+
+    /// Number of words that are ignored
+    pub fn ignored_word_count(&self) -> usize {
+        self.ignored_locations
+            .iter()
+            .map(|(start, end)| end - start + 1)
+            .sum()
+    }
+
+    /// Calculates how much of the text is covered by these (inclusive) ranges of words.
+    ///     The adjusted percentage leaves out ignored words, and stopwords if asked to.
+    pub fn coverage<'a>(
+        &self,
+        locations: impl IntoIterator<Item = &'a FragmentLocation>,
+        exclude_stopwords: bool,
+    ) -> Coverage {
+        let mut matched = vec![false; self.words.len()];
+        for (start, end) in locations {
+            for is_matched in &mut matched[*start..=*end] {
+                *is_matched = true;
+            }
+        }
+        let mut checked = vec![true; self.words.len()];
+        for (start, end) in &self.ignored_locations {
+            for is_checked in &mut checked[*start..=*end] {
+                *is_checked = false;
+            }
+        }
+        if exclude_stopwords {
+            for (is_checked, word) in checked.iter_mut().zip(&self.words) {
+                *is_checked = *is_checked && !is_stopword(word);
+            }
+        }
+
+        let percent = |part: usize, whole: usize| {
+            if whole == 0 {
+                0.0
+            } else {
+                (part as f32) / (whole as f32) * 100.0
+            }
+        };
+        let matched_count = matched.iter().filter(|is_matched| **is_matched).count();
+        let checked_count = checked.iter().filter(|is_checked| **is_checked).count();
+        let matched_checked_count = matched
+            .iter()
+            .zip(&checked)
+            .filter(|(is_matched, is_checked)| **is_matched && **is_checked)
+            .count();
+        Coverage {
+            raw_percent: percent(matched_count, self.words.len()),
+            adjusted_percent: percent(matched_checked_count, checked_count),
+        }
+    }
+
+    /// Gets the page number that the word at this index came from,
+    ///     or None if its file has no pages
+    pub fn page_at(&self, word_index: usize) -> Option<usize> {
+        let file_start = self
+            .file_starts
+            .iter()
+            .rev()
+            .find(|(_, start)| *start <= word_index)
+            .map_or(0, |(_, start)| *start);
+        self.page_starts
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= word_index)
+            .filter(|(start, _)| *start >= file_start)
+            .map(|(_, page)| *page)
+    }
 }
 
 /// A single user's "submission" or text string, broken into fragments
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 struct TextEntry {
     owner: TextOwnerID,
+    /// Language the text was detected (or declared) to be written in
+    language: Language,
+    /// Term an archived submission was made in, None for current texts
+    term: Option<String>,
     /// Cleaned text (word-by-word) for usage in printing
-    clean_text_words: Vec<String>,
-    /// Unique string fragments in the text
+    clean_text: CleanText,
+    /// Unique string fragments in the text, leaving out ignored fragments.
+    ///     Not saved in indexes, as it depends on the ignored text
+    #[serde(skip)]
     fragments: HashSet<String>,
     /// Mapping between fragment strings and where in the text they are located
     fragment_locations: HashMap<String, Vec<FragmentLocation>>,
 }
 
-/// Stores the corpus of trusted and untrusted strings
+/// Summary of one text in a database
 #[derive(Debug)]
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+pub struct TextSummary {
+    pub owner: TextOwnerID,
+    pub category: TextCategory,
+    /// Term an archived submission was made in
+    pub term: Option<String>,
+    /// Number of words in the cleaned text
+    pub words: usize,
+}
+
+/// The trusted and archived texts of a database, as saved in an on-disk index so that
+///     they don't need to be read and fragmented again on every run
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IndexedTexts {
+    /// Which part of each text is indexed
+    pub channel: Channel,
+    /// Fragment size the texts were fragmented with
+    pub n: usize,
+    /// Version of the text normalization the texts were cleaned with
+    pub normalization: u32,
+    texts: Vec<TextEntry>,
+    archived_texts: Vec<TextEntry>,
+}
+
+impl IndexedTexts {
+    /// Number of texts in the index
+    pub fn text_count(&self) -> usize {
+        self.texts.len() + self.archived_texts.len()
+    }
+}
+
+/// Stores the corpus of trusted and untrusted strings
+#[derive(Serialize, Deserialize, Debug)]
 pub struct PlagiarismDatabase {
     // Constant value for ngram size
     n: usize,
@@ -49,8 +329,32 @@ pub struct PlagiarismDatabase {
     trusted_texts: HashMap<TextOwnerID, TextEntry>,
     /// Mapping owner ID to the processed text entry for that owner
     untrusted_texts: HashMap<TextOwnerID, TextEntry>,
+    /// Mapping owner ID to the processed text entry for each archived submission
+    ///     from a past term
+    archived_texts: HashMap<TextOwnerID, TextEntry>,
     /// Mapping owner ID to the text contents to ignore
     ignored_texts: HashSet<String>,
+    /// Metric and cutoff value used to decide if a fragment matches ignored text
+    ignore_metric: Metric,
+    ignore_s: usize,
+    /// Whether texts in different languages should be compared to each other
+    cross_language: bool,
+    /// Which part of each text this database holds
+    channel: Channel,
+    /// Mapping owner ID to the group (or team) the owner is a member of
+    #[serde(skip)]
+    groups: HashMap<TextOwnerID, String>,
+    /// Whether untrusted texts from members of the same group are compared at all
+    #[serde(skip)]
+    skip_same_group: bool,
+    /// Whether stopwords are left out of adjusted coverage percentages
+    exclude_stopwords: bool,
+    /// If set, only pairs that include one of these untrusted owners are checked
+    #[serde(skip)]
+    checked_owners: Option<HashSet<TextOwnerID>>,
+    /// Progress bar ticked once for every pair of texts compared
+    #[serde(skip)]
+    progress: Option<ProgressBar>,
 }
 
 impl PlagiarismDatabase {
@@ -69,7 +373,127 @@ impl PlagiarismDatabase {
             metric,
             trusted_texts: HashMap::new(),
             untrusted_texts: HashMap::new(),
+            archived_texts: HashMap::new(),
             ignored_texts: PlagiarismDatabase::construct_ignored_texts(&ignored_texts, n),
+            ignore_metric: Metric::Equal,
+            ignore_s: s,
+            cross_language: false,
+            channel: Channel::Content,
+            groups: HashMap::new(),
+            skip_same_group: false,
+            exclude_stopwords: false,
+            checked_owners: None,
+            progress: None,
+        }
+    }
+
+    /// Marks this database as holding a particular part of each text,
+    ///     which is recorded on every result it produces
+    pub fn set_channel(&mut self, channel: Channel) {
+        self.channel = channel;
+    }
+
+    /// Allows texts written in different languages to be compared.
+    ///     By default, only texts of the same language are compared.
+    pub fn set_cross_language(&mut self, cross_language: bool) {
+        self.cross_language = cross_language;
+    }
+
+    /// Leaves stopwords out of the adjusted coverage percentage of each result,
+    ///     as well as ignored words
+    pub fn set_exclude_stopwords(&mut self, exclude_stopwords: bool) {
+        self.exclude_stopwords = exclude_stopwords;
+    }
+
+    /// Sets the metric and cutoff value used to match fragments against ignored text,
+    ///     which are exact matching and the plagiarism cutoff by default. Must be set
+    ///     before texts are added.
+    pub fn set_ignore_metric(&mut self, metric: Metric, s: usize) {
+        self.ignore_metric = metric;
+        self.ignore_s = s;
+    }
+
+    /// Checks if a fragment matches any ignored text under the ignore metric,
+    ///     so that e.g. a template with a typo fixed is still ignored
+    fn is_ignored(&self, fragment: &str) -> bool {
+        self.ignored_texts.contains(fragment)
+            || (self.ignore_metric != Metric::Equal
+                && self.ignored_texts.par_iter().any(|ignored| {
+                    is_plagiarised(fragment, ignored, self.ignore_metric, self.ignore_s)
+                }))
+    }
+
+    /// Sets the group each untrusted owner is a member of. Members of the same
+    ///     group are not compared to each other if skip_same_group is set,
+    ///     otherwise their results are marked as being from the same group
+    pub fn set_groups(&mut self, groups: HashMap<TextOwnerID, String>, skip_same_group: bool) {
+        self.groups = groups;
+        self.skip_same_group = skip_same_group;
+    }
+
+    /// Only checks pairs that include at least one of these untrusted owners, e.g.
+    ///     the submissions that are new since the last run
+    pub fn set_checked_owners(&mut self, owners: HashSet<TextOwnerID>) {
+        self.checked_owners = Some(owners);
+    }
+
+    /// Ticks this progress bar once for every pair of texts compared by the checks
+    pub fn set_progress(&mut self, progress: ProgressBar) {
+        self.progress = Some(progress);
+    }
+
+    /// Counts the pairs of texts the checks will compare, e.g. for a progress bar.
+    ///     Pairs of trusted texts are counted too if with_trusted_overlap is set
+    pub fn count_pairs(&self, with_trusted_overlap: bool) -> u64 {
+        let untrusted: Vec<&TextOwnerID> = self.untrusted_texts.keys().collect();
+        let mut pairs = 0;
+        for (i, owner1) in untrusted.iter().enumerate() {
+            for owner2 in &untrusted[i + 1..] {
+                if (self.is_checked(owner1) || self.is_checked(owner2))
+                    && !(self.skip_same_group && self.in_same_group(owner1, owner2))
+                {
+                    pairs += 1;
+                }
+            }
+        }
+        let checked = untrusted
+            .iter()
+            .filter(|owner| self.is_checked(owner))
+            .count();
+        pairs += checked * (self.trusted_texts.len() + self.archived_texts.len());
+        if with_trusted_overlap {
+            let trusted = self.trusted_texts.len();
+            pairs += trusted * trusted.saturating_sub(1) / 2;
+        }
+        pairs as u64
+    }
+
+    /// Counts the words and distinct fragments over every text in the database
+    pub fn count_words_and_fragments(&self) -> (usize, usize) {
+        self.untrusted_texts
+            .values()
+            .chain(self.trusted_texts.values())
+            .chain(self.archived_texts.values())
+            .fold((0, 0), |(words, fragments), entry| {
+                (
+                    words + entry.clean_text.words.len(),
+                    fragments + entry.fragments.len(),
+                )
+            })
+    }
+
+    /// Checks if pairs including this untrusted owner should be checked
+    fn is_checked(&self, owner: &str) -> bool {
+        self.checked_owners
+            .as_ref()
+            .is_none_or(|owners| owners.contains(owner))
+    }
+
+    /// Checks if both owners are members of the same group
+    fn in_same_group(&self, owner1: &str, owner2: &str) -> bool {
+        match (self.groups.get(owner1), self.groups.get(owner2)) {
+            (Some(group1), Some(group2)) => group1 == group2,
+            _ => false,
         }
     }
 
@@ -87,59 +511,290 @@ impl PlagiarismDatabase {
         ignored_text_set
     }
 
-    /// Gets only the ID -> clean text mapping for all texts
-    pub fn get_all_cleantext(&self) -> HashMap<TextOwnerID, Vec<String>> {
-        let trusted = self
-            .trusted_texts
-            .iter()
-            .map(|(k, v)| (k.clone(), v.clean_text_words.clone()));
-        let untrusted = self
+    /// Ignores fragments found in more than max_frequency (0 to 1) of the untrusted
+    ///     texts, such as assignment prompts or code scaffolding. Must be called
+    ///     after all texts are added. Returns the ignored fragments, most common first
+    pub fn ignore_common_fragments(&mut self, max_frequency: f32) -> Vec<CommonFragment> {
+        let mut text_counts: HashMap<&str, usize> = HashMap::new();
+        for entry in self.untrusted_texts.values() {
+            for fragment in &entry.fragments {
+                *text_counts.entry(fragment).or_default() += 1;
+            }
+        }
+        let max_texts = max_frequency * self.untrusted_texts.len() as f32;
+        let mut common: Vec<CommonFragment> = text_counts
+            .into_iter()
+            .filter(|(_, texts)| *texts >= MIN_COMMON_FRAGMENT_TEXTS && *texts as f32 > max_texts)
+            .map(|(fragment, texts)| CommonFragment {
+                fragment: fragment.to_string(),
+                texts,
+                channel: self.channel,
+            })
+            .collect();
+        common.sort_by(|a, b| {
+            b.texts
+                .cmp(&a.texts)
+                .then_with(|| a.fragment.cmp(&b.fragment))
+        });
+
+        for entry in self
             .untrusted_texts
+            .values_mut()
+            .chain(self.trusted_texts.values_mut())
+            .chain(self.archived_texts.values_mut())
+        {
+            for fragment in &common {
+                if entry.fragments.remove(&fragment.fragment) {
+                    let locations = entry.fragment_locations[&fragment.fragment].clone();
+                    entry.clean_text.add_ignored_locations(locations);
+                }
+            }
+        }
+        self.ignored_texts
+            .extend(common.iter().map(|fragment| fragment.fragment.clone()));
+        common
+    }
+
+    /// Gets only the ID -> clean text mapping for all texts
+    pub fn get_all_cleantext(&self) -> HashMap<TextOwnerID, CleanText> {
+        self.trusted_texts
             .iter()
-            .map(|(k, v)| (k.clone(), v.clean_text_words.clone()));
-        trusted.chain(untrusted).collect()
-    }
-
-    /// Adds a text string as potential plagiarism source material
-    pub fn add_trusted_text(&mut self, owner_id: &str, text: &str) {
-        let clean_text_words = clean_text(text);
-        let (mut fragments, fragment_locations) =
-            PlagiarismDatabase::get_textfragments(&clean_text_words, self.n);
-        // Remove strings that match the ignored list (equality test directly)
-        fragments = fragments
-            .difference(&self.ignored_texts)
-            .map(String::from)
-            .collect();
-        self.trusted_texts.insert(
-            owner_id.to_string(),
-            TextEntry {
-                owner: owner_id.to_string(),
-                clean_text_words,
-                fragments,
-                fragment_locations,
-            },
-        );
+            .chain(self.untrusted_texts.iter())
+            .chain(self.archived_texts.iter())
+            .map(|(k, v)| (k.clone(), v.clean_text.clone()))
+            .collect()
     }
 
-    /// Adds a text string as a potential plagiarized string
-    pub fn add_untrusted_text(&mut self, owner_id: &str, text: &str) {
-        let clean_text_words = clean_text(text);
-        let (mut fragments, fragment_locations) =
-            PlagiarismDatabase::get_textfragments(&clean_text_words, self.n);
-        // Remove strings that match the ignored list (equality test directly)
-        fragments = fragments
-            .difference(&self.ignored_texts)
-            .map(String::from)
-            .collect();
-        self.untrusted_texts.insert(
-            owner_id.to_string(),
-            TextEntry {
-                owner: owner_id.to_string(),
-                clean_text_words,
-                fragments,
-                fragment_locations,
-            },
+    /// Copies the trusted and archived texts so that they can be saved to an index
+    pub fn get_indexed_texts(&self) -> IndexedTexts {
+        let copy_entries = |texts: &HashMap<TextOwnerID, TextEntry>| {
+            let mut entries: Vec<TextEntry> = texts
+                .values()
+                .map(|entry| TextEntry {
+                    owner: entry.owner.clone(),
+                    language: entry.language,
+                    term: entry.term.clone(),
+                    clean_text: entry.clean_text.clone(),
+                    fragments: HashSet::new(),
+                    fragment_locations: entry.fragment_locations.clone(),
+                })
+                .collect();
+            entries.sort_by(|a, b| a.owner.cmp(&b.owner));
+            entries
+        };
+        IndexedTexts {
+            channel: self.channel,
+            n: self.n,
+            normalization: NORMALIZATION_VERSION,
+            texts: copy_entries(&self.trusted_texts),
+            archived_texts: copy_entries(&self.archived_texts),
+        }
+    }
+
+    /// Adds the texts of an index as trusted and archived texts. The
+    ///     current ignored text is applied to them, whatever was ignored when the
+    ///     index was built. Panics if the index was built for another channel,
+    ///     fragment size or normalization, as its fragments would never match.
+    pub fn add_indexed_texts(&mut self, indexed: IndexedTexts) {
+        if indexed.channel != self.channel {
+            panic!(
+                "Index holds {} texts but is being added to the {} texts",
+                indexed.channel.name(),
+                self.channel.name()
+            );
+        }
+        if indexed.n != self.n {
+            panic!(
+                "Index of {} texts was built with n = {}, but n = {} is being used",
+                indexed.channel.name(),
+                indexed.n,
+                self.n
+            );
+        }
+        if indexed.normalization != NORMALIZATION_VERSION {
+            panic!(
+                "Index was built with text normalization version {}, but this version of the \
+                 program uses version {}. Please rebuild the index.",
+                indexed.normalization, NORMALIZATION_VERSION
+            );
+        }
+        let restore = |mut entry: TextEntry| {
+            self.restore_fragments(&mut entry);
+            (entry.owner.clone(), entry)
+        };
+        let texts: Vec<(TextOwnerID, TextEntry)> = indexed.texts.into_iter().map(restore).collect();
+        let archived_texts: Vec<(TextOwnerID, TextEntry)> =
+            indexed.archived_texts.into_iter().map(restore).collect();
+        self.trusted_texts.extend(texts);
+        self.archived_texts.extend(archived_texts);
+    }
+
+    /// Rebuilds the fragments of an entry that was loaded from disk, where they
+    ///     aren't saved, leaving out the fragments that are currently ignored
+    fn restore_fragments(&self, entry: &mut TextEntry) {
+        entry.clean_text.ignored_locations = Vec::new();
+        entry.fragments = entry.fragment_locations.keys().cloned().collect();
+        self.remove_ignored_fragments(entry);
+    }
+
+    /// Rebuilds everything that isn't saved along with a database, after it
+    ///     has been loaded from disk
+    pub fn restore_after_load(&mut self) {
+        let mut all_texts = [
+            std::mem::take(&mut self.trusted_texts),
+            std::mem::take(&mut self.untrusted_texts),
+            std::mem::take(&mut self.archived_texts),
+        ];
+        for texts in &mut all_texts {
+            for entry in texts.values_mut() {
+                self.restore_fragments(entry);
+            }
+        }
+        let [trusted_texts, untrusted_texts, archived_texts] = all_texts;
+        self.trusted_texts = trusted_texts;
+        self.untrusted_texts = untrusted_texts;
+        self.archived_texts = archived_texts;
+    }
+
+    /// Checks if there is a text (of any kind) with this owner ID
+    pub fn has_owner(&self, owner_id: &str) -> bool {
+        self.untrusted_texts.contains_key(owner_id)
+            || self.trusted_texts.contains_key(owner_id)
+            || self.archived_texts.contains_key(owner_id)
+    }
+
+    /// Removes an untrusted text, e.g. a submission that has been withdrawn
+    pub fn remove_untrusted_submission(&mut self, owner_id: &str) {
+        self.untrusted_texts.remove(owner_id);
+    }
+
+    /// Summarizes every text in the database, sorted by category then owner ID
+    pub fn get_text_summaries(&self) -> Vec<TextSummary> {
+        [
+            (TextCategory::Untrusted, &self.untrusted_texts),
+            (TextCategory::Trusted, &self.trusted_texts),
+            (TextCategory::Archived, &self.archived_texts),
+        ]
+        .iter()
+        .flat_map(|(category, texts)| {
+            let mut entries: Vec<&TextEntry> = texts.values().collect();
+            entries.sort_by(|a, b| a.owner.cmp(&b.owner));
+            entries.into_iter().map(move |entry| TextSummary {
+                owner: entry.owner.clone(),
+                category: *category,
+                term: entry.term.clone(),
+                words: entry.clean_text.words.len(),
+            })
+        })
+        .collect()
+    }
+
+    /// Adds several (file name, text) files from one owner as potential plagiarism
+    ///     source material
+    pub fn add_trusted_submission(
+        &mut self,
+        owner_id: &str,
+        files: &[(String, String)],
+        language: Language,
+    ) {
+        let entry = self.construct_text_entry(owner_id, files, language);
+        self.trusted_texts.insert(owner_id.to_string(), entry);
+    }
+
+    /// Adds several (file name, text) files from one owner as a potential plagiarized
+    ///     submission
+    pub fn add_untrusted_submission(
+        &mut self,
+        owner_id: &str,
+        files: &[(String, String)],
+        language: Language,
+    ) {
+        let entry = self.construct_text_entry(owner_id, files, language);
+        self.untrusted_texts.insert(owner_id.to_string(), entry);
+    }
+
+    /// Adds several (file name, text) files from one owner as a submission from a
+    ///     past term, which current submissions are checked against
+    pub fn add_archived_submission(
+        &mut self,
+        owner_id: &str,
+        term: &str,
+        files: &[(String, String)],
+        language: Language,
+    ) {
+        let mut entry = self.construct_text_entry(owner_id, files, language);
+        entry.term = Some(term.to_string());
+        self.archived_texts.insert(owner_id.to_string(), entry);
+    }
+
+    /// Cleans and fragments each file separately (so that no fragment spans two files),
+    ///     then joins them into a single entry with the locations offset to match.
+    ///     Files with page breaks (e.g. PDFs) have the start of each page recorded
+    fn construct_text_entry(
+        &self,
+        owner_id: &str,
+        files: &[(String, String)],
+        language: Language,
+    ) -> TextEntry {
+        let mut clean_text_words: Vec<String> = Vec::new();
+        let mut file_starts: Vec<(String, usize)> = Vec::new();
+        let mut page_starts: Vec<(usize, usize)> = Vec::new();
+        let mut fragments: HashSet<String> = HashSet::new();
+        let mut fragment_locations: HashMap<String, Vec<FragmentLocation>> = HashMap::new();
+        for (file_name, text) in files {
+            let offset = clean_text_words.len();
+            let mut file_words: Vec<String> = Vec::new();
+            let pages: Vec<&str> = text.split(PAGE_SEPARATOR).collect();
+            for (i, page) in pages.iter().enumerate() {
+                if pages.len() > 1 {
+                    page_starts.push((offset + file_words.len(), i + 1));
+                }
+                file_words.extend(clean_text(page));
+            }
+            let (file_fragments, file_fragment_locations) =
+                PlagiarismDatabase::get_textfragments(&file_words, self.n);
+            fragments.extend(file_fragments);
+            for (fragment, locations) in file_fragment_locations {
+                fragment_locations.entry(fragment).or_default().extend(
+                    locations
+                        .iter()
+                        .map(|(start, end)| (start + offset, end + offset)),
+                );
+            }
+            file_starts.push((file_name.clone(), offset));
+            clean_text_words.extend(file_words);
+        }
+        let clean_text = CleanText {
+            words: clean_text_words,
+            file_starts,
+            page_starts,
+            ignored_locations: Vec::new(),
+        };
+        let mut entry = TextEntry {
+            owner: owner_id.to_string(),
+            language,
+            term: None,
+            clean_text,
+            fragments,
+            fragment_locations,
+        };
+        self.remove_ignored_fragments(&mut entry);
+        entry
+    }
+
+    /// Removes fragments that match the ignored list, remembering where they were
+    fn remove_ignored_fragments(&self, entry: &mut TextEntry) {
+        let (ignored, fragments): (HashSet<String>, HashSet<String>) = entry
+            .fragments
+            .drain()
+            .partition(|fragment| self.is_ignored(fragment));
+        let fragment_locations = &entry.fragment_locations;
+        entry.clean_text.add_ignored_locations(
+            ignored
+                .iter()
+                .flat_map(|fragment| fragment_locations[fragment].iter().copied()),
         );
+        entry.fragments = fragments;
     }
 
     /// Check for plagiarism by comparing metric against cutoff
@@ -149,7 +804,15 @@ impl PlagiarismDatabase {
         // .skip() in second loop to avoid checking same combinations twice
         for (sourceidx, source) in self.untrusted_texts.values().enumerate() {
             for against in self.untrusted_texts.values().skip(sourceidx + 1) {
-                if let Some(result) = self.run_metrics(source, against, false) {
+                if !self.is_checked(&source.owner) && !self.is_checked(&against.owner) {
+                    continue;
+                }
+                let same_group = self.in_same_group(&source.owner, &against.owner);
+                if same_group && self.skip_same_group {
+                    continue;
+                }
+                if let Some(mut result) = self.run_metrics(source, against, false) {
+                    result.same_group = same_group;
                     results.push(result);
                 }
             }
@@ -163,6 +826,41 @@ impl PlagiarismDatabase {
         let mut results: Vec<PlagiarismResult> = Vec::new();
         for source in self.trusted_texts.values() {
             for against in self.untrusted_texts.values() {
+                if !self.is_checked(&against.owner) {
+                    continue;
+                }
+                if let Some(result) = self.run_metrics(source, against, true) {
+                    results.push(result);
+                }
+            }
+        }
+        results
+    }
+
+    /// Check for plagiarism by comparing metric against cutoff
+    ///     for textfragments in database against archived submissions from past terms.
+    ///     Archived submissions are not compared to each other.
+    pub fn check_archive_plagiarism(&self) -> Vec<PlagiarismResult> {
+        let mut results: Vec<PlagiarismResult> = Vec::new();
+        for source in self.archived_texts.values() {
+            for against in self.untrusted_texts.values() {
+                if !self.is_checked(&against.owner) {
+                    continue;
+                }
+                if let Some(result) = self.run_metrics(source, against, false) {
+                    results.push(result);
+                }
+            }
+        }
+        results
+    }
+
+    /// Compares every pair of trusted texts to find sources that overlap,
+    ///     e.g. a textbook that quotes a Wikipedia article
+    pub fn check_trusted_overlap(&self) -> Vec<PlagiarismResult> {
+        let mut results: Vec<PlagiarismResult> = Vec::new();
+        for (sourceidx, source) in self.trusted_texts.values().enumerate() {
+            for against in self.trusted_texts.values().skip(sourceidx + 1) {
                 if let Some(result) = self.run_metrics(source, against, true) {
                     results.push(result);
                 }
@@ -171,6 +869,97 @@ impl PlagiarismDatabase {
         results
     }
 
+    /// Attributes each passage of an untrusted text that matches several overlapping
+    ///     trusted sources (as found by check_trusted_overlap) to just one of them,
+    ///     instead of reporting it once per source. The best source is the one that
+    ///     covers the most of the untrusted text, then the one with the earliest date
+    ///     (e.g. of publication) if given, then the first by ID.
+    ///     Results left with no matches of their own are dropped.
+    pub fn attribute_to_best_sources(
+        &self,
+        results: Vec<PlagiarismResult>,
+        overlaps: &[PlagiarismResult],
+        source_dates: &HashMap<TextOwnerID, String>,
+    ) -> Vec<PlagiarismResult> {
+        let overlapping: HashSet<(&str, &str)> = overlaps
+            .iter()
+            .flat_map(|overlap| {
+                vec![
+                    (overlap.owner_id1.as_str(), overlap.owner_id2.as_str()),
+                    (overlap.owner_id2.as_str(), overlap.owner_id1.as_str()),
+                ]
+            })
+            .collect();
+        let mut untrusted_results: HashMap<TextOwnerID, Vec<PlagiarismResult>> = HashMap::new();
+        for result in results {
+            untrusted_results
+                .entry(result.owner_id2.clone())
+                .or_default()
+                .push(result);
+        }
+
+        let mut attributed: Vec<PlagiarismResult> = Vec::new();
+        for (_, mut results) in untrusted_results {
+            results.sort_by(|a, b| a.cmp_sources(b, source_dates));
+            // Sources that each fragment of the untrusted text is attributed to
+            let mut attributions: HashMap<String, Vec<TextOwnerID>> = HashMap::new();
+            for mut result in results {
+                let source = result.owner_id1.clone();
+                let keep: Vec<bool> = result
+                    .matching_fragments
+                    .iter()
+                    .map(|(_, fragment)| {
+                        !attributions.get(fragment).is_some_and(|sources| {
+                            sources.iter().any(|other| {
+                                overlapping.contains(&(other.as_str(), source.as_str()))
+                            })
+                        })
+                    })
+                    .collect();
+                for ((_, fragment), keep) in result.matching_fragments.iter().zip(&keep) {
+                    if *keep {
+                        attributions
+                            .entry(fragment.clone())
+                            .or_default()
+                            .push(source.clone());
+                    }
+                }
+                if self.retain_matches(&mut result, &keep) {
+                    attributed.push(result);
+                }
+            }
+        }
+        attributed
+    }
+
+    /// Keeps only the matches of a trusted result that are marked to be kept,
+    ///     updating its coverage to match. Returns whether any matches are left.
+    fn retain_matches(&self, result: &mut PlagiarismResult, keep: &[bool]) -> bool {
+        fn retain<T>(items: &mut Vec<T>, keep: &[bool]) {
+            let mut keep = keep.iter();
+            items.retain(|_| *keep.next().unwrap_or(&true));
+        }
+        retain(&mut result.matching_fragments, keep);
+        retain(&mut result.matching_fragments_locations, keep);
+        retain(&mut result.matching_fragments_files, keep);
+        retain(&mut result.matching_fragments_pages, keep);
+        result.coverage1 = self.trusted_texts[&result.owner_id1].clean_text.coverage(
+            result
+                .matching_fragments_locations
+                .iter()
+                .flat_map(|(locs1, _)| locs1),
+            self.exclude_stopwords,
+        );
+        result.coverage2 = self.untrusted_texts[&result.owner_id2].clean_text.coverage(
+            result
+                .matching_fragments_locations
+                .iter()
+                .flat_map(|(_, locs2)| locs2),
+            self.exclude_stopwords,
+        );
+        !result.matching_fragments.is_empty()
+    }
+
     /// Helper function to actually run the plagiarism check against sources
     fn run_metrics(
         &self,
@@ -178,6 +967,13 @@ impl PlagiarismDatabase {
         against: &TextEntry,
         is_trusted_owner1: bool,
     ) -> Option<PlagiarismResult> {
+        if let Some(progress) = &self.progress {
+            progress.inc(1);
+        }
+        // Don't compare e.g. a Python submission to a C submission unless asked to
+        if !self.cross_language && source.language != against.language {
+            return None;
+        }
         // Run metrics against both sources to get all matching strings
         let matching_fragments = match self.metric {
             Metric::Equal => self.check_plagiarism_equal(source, against),
@@ -188,65 +984,69 @@ impl PlagiarismDatabase {
             return None;
         }
         // Get the locations of each matching fragment from each source text
-        let matching_fragments_locations = matching_fragments
+        let matching_fragments_locations: Vec<(Vec<FragmentLocation>, Vec<FragmentLocation>)> =
+            matching_fragments
             .iter()
             .map(|(f1, f2)| {
-                if is_trusted_owner1 {
-                    self.fragments_to_locations_trusted(f1, &source.owner, f2, &against.owner)
-                } else {
-                    self.fragments_to_locations(f1, &source.owner, f2, &against.owner)
-                }
+                (
+                    source.fragment_locations[f1].clone(),
+                    against.fragment_locations[f2].clone(),
+                )
+            })
+            .collect();
+        // Name the file each match was (first) found in
+        let matching_fragments_files = matching_fragments_locations
+            .iter()
+            .map(|(locs1, locs2)| {
+                (
+                    source.clean_text.file_at(locs1[0].0).to_string(),
+                    against.clean_text.file_at(locs2[0].0).to_string(),
+                )
             })
             .collect();
+        // and the page, for texts that have pages
+        let matching_fragments_pages = matching_fragments_locations
+            .iter()
+            .map(|(locs1, locs2)| {
+                (
+                    source.clean_text.page_at(locs1[0].0),
+                    against.clean_text.page_at(locs2[0].0),
+                )
+            })
+            .collect();
+        // Work out how much of each text the matches cover
+        let coverage1 = source.clean_text.coverage(
+            matching_fragments_locations.iter().flat_map(|(locs1, _)| locs1),
+            self.exclude_stopwords,
+        );
+        let coverage2 = against.clean_text.coverage(
+            matching_fragments_locations.iter().flat_map(|(_, locs2)| locs2),
+            self.exclude_stopwords,
+        );
         // Construct result
         let result = PlagiarismResult {
             owner_id1: source.owner.clone(),
             owner_id2: against.owner.clone(),
+            channel: self.channel,
             matching_fragments_locations,
             matching_fragments,
+            matching_fragments_files,
+            matching_fragments_pages,
+            coverage1,
+            coverage2,
             trusted_owner1: is_trusted_owner1,
             equal_fragments: self.metric == Metric::Equal,
+            same_group: false,
+            term1: source.term.clone(),
         };
         Some(result)
     }
 
-    /// Takes in a separated tuple of matching fragments and their owner IDs.
-    ///     Returns vectors representing where they can be found in their respective texts
-    fn fragments_to_locations(
-        &self,
-        f1: &str,
-        owner1: &str,
-        f2: &str,
-        owner2: &str,
-    ) -> (Vec<FragmentLocation>, Vec<FragmentLocation>) {
-        let f1_locations: Vec<FragmentLocation> =
-            self.untrusted_texts[owner1].fragment_locations[f1].clone();
-        let f2_locations: Vec<FragmentLocation> =
-            self.untrusted_texts[owner2].fragment_locations[f2].clone();
-        (f1_locations, f2_locations)
-    }
-    /// Takes in a separated tuple of matching fragments and their owner IDs.
-    ///     Returns vectors representing where they can be found in their respective texts
-    ///     This checks the trusted_texts map for the first owner
-    fn fragments_to_locations_trusted(
-        &self,
-        f1: &str,
-        owner1: &str,
-        f2: &str,
-        owner2: &str,
-    ) -> (Vec<FragmentLocation>, Vec<FragmentLocation>) {
-        let f1_locations: Vec<FragmentLocation> =
-            self.trusted_texts[owner1].fragment_locations[f1].clone();
-        let f2_locations: Vec<FragmentLocation> =
-            self.untrusted_texts[owner2].fragment_locations[f2].clone();
-        (f1_locations, f2_locations)
-    }
-
     /// Splits a text string into separate ngram TextFragments
     ///     Also creates the map of fragments -> locations at the same time before
     ///     vector location information is lost
     fn get_textfragments(
-        words: &Vec<String>,
+        words: &[String],
         n: usize,
     ) -> (HashSet<String>, HashMap<String, Vec<FragmentLocation>>) {
         let ngrams = extract_clean_word_ngrams(words, n);
@@ -262,8 +1062,7 @@ impl PlagiarismDatabase {
                     })
                     .push((start_location, start_location + n - 1));
             } else {
-                let mut loc_vec: Vec<FragmentLocation> = Vec::new();
-                loc_vec.push((start_location, start_location + n - 1));
+                let loc_vec: Vec<FragmentLocation> = vec![(start_location, start_location + n - 1)];
                 fragment_locations.insert(ngram.to_string(), loc_vec);
             }
         }
@@ -313,3 +1112,252 @@ impl PlagiarismDatabase {
         results
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_at() {
+        let db = PlagiarismDatabase::new(2, 0, Metric::Equal, Vec::new());
+        let entry = db.construct_text_entry(
+            "alice",
+            &[
+                ("notes.txt".to_string(), "one two three".to_string()),
+                ("report.pdf".to_string(), "four five\x0csix seven".to_string()),
+            ],
+            Language::Text,
+        );
+        let text = &entry.clean_text;
+        assert_eq!(text.page_starts, vec![(3, 1), (5, 2)]);
+        assert_eq!(text.file_at(1), "notes.txt");
+        assert_eq!(text.page_at(1), None);
+        assert_eq!(text.file_at(4), "report.pdf");
+        assert_eq!(text.page_at(4), Some(1));
+        assert_eq!(text.page_at(6), Some(2));
+    }
+
+    #[test]
+    fn test_ignored_locations() {
+        let ignored = vec!["answer the question below".to_string()];
+        let files = vec![(
+            "alice".to_string(),
+            "my answer: answer the question below, then stop".to_string(),
+        )];
+        let db = PlagiarismDatabase::new(2, 0, Metric::Equal, ignored);
+        let entry = db.construct_text_entry("alice", &files, Language::Text);
+        assert_eq!(entry.clean_text.ignored_locations, vec![(2, 5)]);
+        assert_eq!(entry.clean_text.ignored_word_count(), 4);
+        assert!(!entry.fragments.contains("the question"));
+        assert!(entry.fragments.contains("below then"));
+    }
+
+    #[test]
+    fn test_attribute_to_best_sources() {
+        let mut db = PlagiarismDatabase::new(3, 0, Metric::Equal, Vec::new());
+        let add_trusted = |db: &mut PlagiarismDatabase, owner: &str, text: &str| {
+            db.add_trusted_submission(
+                owner,
+                &[(owner.to_string(), text.to_string())],
+                Language::Text,
+            );
+        };
+        add_trusted(
+            &mut db,
+            "wikipedia",
+            "rust is a systems programming language",
+        );
+        add_trusted(
+            &mut db,
+            "textbook",
+            "as they say rust is a systems programming language indeed",
+        );
+        add_trusted(
+            &mut db,
+            "blog",
+            "completely unrelated words about cooking pasta",
+        );
+        db.add_untrusted_submission(
+            "alice",
+            &[(
+                "alice".to_string(),
+                "i think rust is a systems programming language indeed".to_string(),
+            )],
+            Language::Text,
+        );
+        let overlaps = db.check_trusted_overlap();
+        assert_eq!(overlaps.len(), 1);
+
+        let results = db.check_trusted_plagiarism();
+        assert_eq!(results.len(), 2);
+        // The textbook covers more of alice's text, so every shared passage is
+        // attributed to it and the wikipedia result is dropped
+        let attributed = db.attribute_to_best_sources(results, &overlaps, &HashMap::new());
+        assert_eq!(attributed.len(), 1);
+        assert_eq!(attributed[0].owner_id1, "textbook");
+        assert_eq!(attributed[0].matching_fragments.len(), 5);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let ignored = vec!["answer the question".to_string()];
+        let files = vec![(
+            "alice".to_string(),
+            "answer the question: the cat sat on a mat".to_string(),
+        )];
+        let db = PlagiarismDatabase::new(3, 0, Metric::Equal, ignored);
+        let text = db.construct_text_entry("alice", &files, Language::Text).clean_text;
+        // "the cat sat" out of 9 words, or of the 6 words that aren't ignored
+        let coverage = text.coverage(&[(3, 5)], false);
+        assert!((coverage.raw_percent - 100.0 / 3.0).abs() < 0.01);
+        assert!((coverage.adjusted_percent - 50.0).abs() < 0.01);
+        // "cat sat" out of "cat sat mat"
+        let coverage = text.coverage(&[(3, 5)], true);
+        assert!((coverage.adjusted_percent - 200.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_fuzzy_ignored_text() {
+        let ignored = vec!["Explain the diference between a stack and a queue".to_string()];
+        let text = "explain the difference between a stack and a queue".to_string();
+        let files = vec![("alice".to_string(), text)];
+        let mut db = PlagiarismDatabase::new(4, 0, Metric::Equal, ignored);
+        let entry = db.construct_text_entry("alice", &files, Language::Text);
+        assert!(entry.fragments.contains("the difference between a"));
+        db.set_ignore_metric(Metric::Lev, 1);
+        let entry = db.construct_text_entry("alice", &files, Language::Text);
+        assert!(entry.fragments.is_empty());
+    }
+
+    #[test]
+    fn test_ignore_common_fragments() {
+        let mut db = PlagiarismDatabase::new(3, 0, Metric::Equal, Vec::new());
+        for (owner, text) in [
+            ("a", "answer the question below: alice was here"),
+            ("b", "answer the question below: bob was here"),
+            (
+                "c",
+                "answer the question below: carol copied alice was here",
+            ),
+            ("d", "something else entirely"),
+        ] {
+            db.add_untrusted_submission(
+                owner,
+                &[(owner.to_string(), text.to_string())],
+                Language::Text,
+            );
+        }
+        let common: Vec<(String, usize)> = db
+            .ignore_common_fragments(0.5)
+            .into_iter()
+            .map(|fragment| (fragment.fragment, fragment.texts))
+            .collect();
+        assert_eq!(
+            common,
+            vec![
+                ("answer the question".to_string(), 3),
+                ("the question below".to_string(), 3),
+            ]
+        );
+        // Shared by only two texts, so still reported
+        let results = db.check_untrusted_plagiarism();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matching_fragments.len(), 1);
+    }
+
+    #[test]
+    fn test_common_fragment_channel_name() {
+        let fragment = CommonFragment {
+            fragment: "answer the question".to_string(),
+            texts: 3,
+            channel: Channel::Comments,
+        };
+        let json = serde_json::to_value(&fragment).unwrap();
+        assert_eq!(json["channel"], Channel::Comments.name());
+    }
+
+    #[test]
+    fn test_check_archive_plagiarism() {
+        let mut db = PlagiarismDatabase::new(3, 0, Metric::Equal, Vec::new());
+        let text = "the mitochondria is the powerhouse of the cell";
+        for (owner, term) in [("2022S1/alice.txt", "2022S1"), ("2023S1/bob.txt", "2023S1")] {
+            db.add_archived_submission(
+                owner,
+                term,
+                &[(owner.to_string(), text.to_string())],
+                Language::Text,
+            );
+        }
+        db.add_untrusted_submission(
+            "carol.txt",
+            &[("carol.txt".to_string(), text.to_string())],
+            Language::Text,
+        );
+        // Archived submissions are identical but only compared to current ones
+        let mut results: Vec<(String, Option<String>, String)> = db
+            .check_archive_plagiarism()
+            .into_iter()
+            .map(|result| (result.owner_id1, result.term1, result.owner_id2))
+            .collect();
+        results.sort();
+        assert_eq!(
+            results,
+            vec![
+                (
+                    "2022S1/alice.txt".to_string(),
+                    Some("2022S1".to_string()),
+                    "carol.txt".to_string()
+                ),
+                (
+                    "2023S1/bob.txt".to_string(),
+                    Some("2023S1".to_string()),
+                    "carol.txt".to_string()
+                ),
+            ]
+        );
+        assert!(db.check_untrusted_plagiarism().is_empty());
+        assert!(db.check_trusted_plagiarism().is_empty());
+    }
+
+    #[test]
+    fn test_count_pairs() {
+        let mut db = PlagiarismDatabase::new(3, 0, Metric::Equal, Vec::new());
+        let text = "the mitochondria is the powerhouse of the cell";
+        for owner in ["alice.txt", "bob.txt", "carol.txt"] {
+            db.add_untrusted_submission(
+                owner,
+                &[(owner.to_string(), text.to_string())],
+                Language::Text,
+            );
+        }
+        for owner in ["wiki.txt", "blog.txt"] {
+            db.add_trusted_submission(
+                owner,
+                &[(owner.to_string(), text.to_string())],
+                Language::Text,
+            );
+        }
+        // 3 untrusted pairs, 6 untrusted-trusted pairs and 1 trusted pair
+        assert_eq!(db.count_pairs(false), 9);
+        assert_eq!(db.count_pairs(true), 10);
+        assert_eq!(db.count_words_and_fragments(), (40, 30));
+
+        // The progress bar is ticked once for each pair counted
+        let progress = ProgressBar::hidden();
+        db.set_progress(progress.clone());
+        db.check_untrusted_plagiarism();
+        db.check_trusted_plagiarism();
+        db.check_trusted_overlap();
+        assert_eq!(progress.position(), 10);
+
+        // Members of the same group aren't compared, and only new texts are checked
+        let groups = [("alice.txt", "team"), ("bob.txt", "team")]
+            .iter()
+            .map(|(owner, group)| (owner.to_string(), group.to_string()))
+            .collect();
+        db.set_groups(groups, true);
+        assert_eq!(db.count_pairs(false), 8);
+        db.set_checked_owners(HashSet::from_iter(vec!["carol.txt".to_string()]));
+        assert_eq!(db.count_pairs(false), 4);
+    }
+}
@@ -0,0 +1,108 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::time::{Duration, Instant};
+
+/// Shows a progress bar on the terminal for each phase of a check, and times
+///     each phase for the statistics at the end
+pub struct Progress {
+    /// Whether the bars are drawn at all
+    visible: bool,
+    /// Bar of the current phase
+    bar: ProgressBar,
+    /// Name of the current phase and when it started
+    phase: Option<(&'static str, Instant)>,
+    timings: Vec<(&'static str, Duration)>,
+}
+
+impl Progress {
+    pub fn new(visible: bool) -> Progress {
+        Progress {
+            visible,
+            bar: ProgressBar::hidden(),
+            phase: None,
+            timings: Vec::new(),
+        }
+    }
+
+    /// Ends the current phase (if any) and starts the next one. Returns its bar,
+    ///     which counts up to the given length, or a spinner if the length isn't known.
+    ///     The bar is hidden if progress isn't visible, but can be ticked either way.
+    pub fn start_phase(&mut self, name: &'static str, len: Option<u64>) -> ProgressBar {
+        let bar = match (self.visible, len) {
+            (false, _) => ProgressBar::hidden(),
+            (true, Some(len)) => ProgressBar::new(len).with_style(
+                ProgressStyle::with_template("{msg:20} [{bar:40}] {pos}/{len} ({eta} left)")
+                    .expect("Progress bar template is invalid!")
+                    .progress_chars("=> "),
+            ),
+            (true, None) => {
+                let bar = ProgressBar::new_spinner();
+                bar.enable_steady_tick(Duration::from_millis(100));
+                bar
+            }
+        };
+        bar.set_message(name);
+        self.start(name, bar.clone());
+        bar
+    }
+
+    /// Ends the current phase (if any) and starts the next one without a bar,
+    ///     e.g. for a phase that prints to the terminal itself
+    pub fn start_phase_without_bar(&mut self, name: &'static str) {
+        self.start(name, ProgressBar::hidden());
+    }
+
+    fn start(&mut self, name: &'static str, bar: ProgressBar) {
+        self.finish_phase();
+        self.bar = bar;
+        self.phase = Some((name, Instant::now()));
+    }
+
+    /// Ends the current phase, clearing its bar and noting how long it took
+    pub fn finish_phase(&mut self) {
+        if let Some((name, started)) = self.phase.take() {
+            self.bar.finish_and_clear();
+            self.timings.push((name, started.elapsed()));
+        }
+    }
+
+    /// Ends the current phase and returns how long each phase took, in order
+    pub fn timings(mut self) -> Vec<(&'static str, Duration)> {
+        self.finish_phase();
+        self.timings
+    }
+}
+
+/// Counts and timings of a check, summed up at the end of it
+pub struct Statistics {
+    /// Files that were read, including trusted and archived files
+    pub files: usize,
+    /// Words in all texts that were checked
+    pub words: usize,
+    /// Distinct fragments of n words in all texts that were checked
+    pub fragments: usize,
+    /// Pairs of texts that were compared
+    pub pairs: u64,
+    /// Results found (before any were filtered out of the report)
+    pub results: usize,
+    /// Matching fragments over all of the results
+    pub matching_fragments: usize,
+    /// How long each phase took, in order
+    pub timings: Vec<(&'static str, Duration)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_timings() {
+        let mut progress = Progress::new(false);
+        let bar = progress.start_phase("Reading files", None);
+        bar.inc(1);
+        progress.start_phase("Comparing texts", Some(2)).inc(2);
+        progress.start_phase_without_bar("Reporting");
+        let timings = progress.timings();
+        let names: Vec<&str> = timings.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["Reading files", "Comparing texts", "Reporting"]);
+    }
+}
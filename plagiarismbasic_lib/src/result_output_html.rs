@@ -1,6 +1,10 @@
-use crate::plagiarism_database::{PlagiarismResult, TextOwnerID};
-use crate::text_utils::get_boldtext_segments_from_intervals;
-use fs_extra;
+use crate::attribution::Attribution;
+use crate::file_utils::SkippedFile;
+use crate::manifest::Manifest;
+use crate::plagiarism_database::{
+    Channel, CleanText, CommonFragment, Coverage, PlagiarismResult, TextOwnerID,
+};
+use crate::text_utils::get_text_segments_from_intervals;
 use gcollections::ops::*;
 use handlebars::Handlebars;
 use interval::interval_set::*;
@@ -15,6 +19,8 @@ const TEMPLATE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/templates/repo
 const ASSETS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/");
 const OUTPUT_DIR: &str = "./www/";
 const OUTPUT_FILE_PATH: &str = "./www/report.html";
+// Number of source-N colour classes in styles.css
+const NUM_SOURCE_COLORS: usize = 8;
 
 // Send a set of these over for each text display
 #[derive(Serialize, PartialEq, Eq, Debug)]
@@ -23,6 +29,18 @@ pub struct TextMaybeBold {
     pub text: String,
     /// Should the text be rendered in a bold font?
     pub is_bold: bool,
+    /// Was the text ignored (and so should be rendered greyed out)?
+    pub is_ignored: bool,
+}
+
+/// The text of one file (or page of a file) in a submission, split into bold
+///     and non-bold segments
+#[derive(Serialize, Debug)]
+struct HBFileSection {
+    /// File name and/or page number, only set if the submission has more than
+    ///     one file or page
+    heading: Option<String>,
+    text: Vec<TextMaybeBold>,
 }
 
 /// A plagiarism result that can be formatted by Handlebars
@@ -30,18 +48,107 @@ pub struct TextMaybeBold {
 struct HBPlagiarismResult {
     owner_id1: TextOwnerID,
     owner_id2: TextOwnerID,
+    /// Names of the owners to show, from the manifest if there is one
+    owner_label1: String,
+    owner_label2: String,
+    channel: &'static str,
     trusted_owner1: bool,
+    /// Term of the first owner's submission, if it is archived from a past term
+    term1: Option<String>,
     equal_fragments: bool,
-    text_display1: Vec<TextMaybeBold>,
-    text_display2: Vec<TextMaybeBold>,
+    same_group: bool,
+    text_display1: Vec<HBFileSection>,
+    text_display2: Vec<HBFileSection>,
+    text1_plag_percent: usize,
+    text2_plag_percent: usize,
+    /// Percentage of the checked (e.g. not ignored) words of each text that were
+    ///     plagiarized, only set if it differs from the percentage of all words
+    text1_adjusted_percent: Option<usize>,
+    text2_adjusted_percent: Option<usize>,
+    /// Percentage of each text that was ignored
+    text1_ignored_percent: usize,
+    text2_ignored_percent: usize,
+    /// Percentages for every channel compared between the same two owners,
+    ///     empty if only one channel was compared
+    channel_percents: Vec<HBChannelPercent>,
+}
+
+/// Plagiarism extent of one channel between two owners
+#[derive(Serialize, Clone, Debug)]
+struct HBChannelPercent {
+    channel: &'static str,
     text1_plag_percent: usize,
     text2_plag_percent: usize,
 }
 
+/// Two trusted sources that share text
+#[derive(Serialize, Debug)]
+struct HBSourceOverlap {
+    source_label1: String,
+    source_label2: String,
+    channel: &'static str,
+    num_fragments: usize,
+    source1_percent: usize,
+    source2_percent: usize,
+}
+
+/// A run of words in an attributed text, coloured by the source it came from
+#[derive(Serialize, Debug)]
+struct HBAttributedSegment {
+    text: String,
+    /// CSS class of the source's colour, if the words came from a source
+    source_class: Option<String>,
+}
+
+/// The text of one file (or page of a file) in an attributed text
+#[derive(Serialize, Debug)]
+struct HBAttributedSection {
+    heading: Option<String>,
+    segments: Vec<HBAttributedSegment>,
+}
+
+/// A trusted source in the legend of an attributed text
+#[derive(Serialize, Debug)]
+struct HBAttributedSource {
+    label: String,
+    source_class: String,
+    percent: usize,
+}
+
+/// An untrusted text with the passages attributed to each trusted source coloured in
+#[derive(Serialize, Debug)]
+struct HBAttribution {
+    owner_label: String,
+    channel: &'static str,
+    total_percent: usize,
+    /// Only set if it differs from the percentage of all words
+    total_adjusted_percent: Option<usize>,
+    sources: Vec<HBAttributedSource>,
+    text: Vec<HBAttributedSection>,
+}
+
+/// Everything shown in the report, as formatted by Handlebars
+#[derive(Serialize, Debug)]
+struct HBReport<'a> {
+    results: Vec<HBPlagiarismResult>,
+    skipped_files: &'a [SkippedFile],
+    common_fragments: &'a [CommonFragment],
+    source_overlaps: Vec<HBSourceOverlap>,
+    attributions: Vec<HBAttribution>,
+    settings: Option<&'a str>,
+}
+
 /// Outputs results to html
+#[allow(clippy::too_many_arguments)]
 pub fn output_results(
     results: &mut Vec<PlagiarismResult>,
-    texts: HashMap<TextOwnerID, Vec<String>>,
+    texts: HashMap<Channel, HashMap<TextOwnerID, CleanText>>,
+    skipped_files: &[SkippedFile],
+    common_fragments: &[CommonFragment],
+    overlaps: &[PlagiarismResult],
+    attributions: &[Attribution],
+    manifest: &Manifest,
+    settings: Option<&str>,
     open_html_after: bool,
 ) {
     // We want the results by most significant first (most matches)
@@ -77,45 +184,82 @@ pub fn output_results(
             }
         }
 
-        let numwords1 = text1_intervals.iter().fold(0, |acc, inter| {
-            acc + (bounded::Bounded::upper(inter) - bounded::Bounded::lower(inter) + 1)
-        });
-        let numwords2 = text2_intervals.iter().fold(0, |acc, inter| {
-            acc + (bounded::Bounded::upper(inter) - bounded::Bounded::lower(inter) + 1)
-        });
-
         // Get the actual text fragments based on the intervals we calculated
-        let t1_text = texts.get(&result.owner_id1).unwrap_or_else(|| {
+        let channel_texts = &texts[&result.channel];
+        let t1_text = channel_texts.get(&result.owner_id1).unwrap_or_else(|| {
             panic!(
                 "Could not find text for owner {} in text map",
                 &result.owner_id1
             )
         });
-        let t1_boldtext: Vec<TextMaybeBold> =
-            get_boldtext_segments_from_intervals(&t1_text, &text1_intervals);
+        let t1_boldtext: Vec<HBFileSection> = get_file_sections(t1_text, &text1_intervals);
 
-        let t2_text: &Vec<String> = texts.get(&result.owner_id2).unwrap_or_else(|| {
+        let t2_text: &CleanText = channel_texts.get(&result.owner_id2).unwrap_or_else(|| {
             panic!(
                 "Could not find text for owner {} in text map",
                 &result.owner_id2
             )
         });
-        let t2_boldtext: Vec<TextMaybeBold> =
-            get_boldtext_segments_from_intervals(&t2_text, &text2_intervals);
+        let t2_boldtext: Vec<HBFileSection> = get_file_sections(t2_text, &text2_intervals);
 
         // Add the result to an overall vector to be sent to Handlebars
         plag_results.push(HBPlagiarismResult {
             owner_id1: result.owner_id1.clone(),
             owner_id2: result.owner_id2.clone(),
+            owner_label1: manifest.label(&result.owner_id1),
+            owner_label2: manifest.label(&result.owner_id2),
+            channel: result.channel.name(),
             trusted_owner1: result.trusted_owner1,
+            term1: result.term1.clone(),
             equal_fragments: result.equal_fragments,
+            same_group: result.same_group,
             text_display1: t1_boldtext,
             text_display2: t2_boldtext,
-            text1_plag_percent: ((numwords1 as f32) / (t1_text.len() as f32) * 100.0) as usize,
-            text2_plag_percent: ((numwords2 as f32) / (t2_text.len() as f32) * 100.0) as usize,
+            text1_plag_percent: result.coverage1.raw_percent as usize,
+            text2_plag_percent: result.coverage2.raw_percent as usize,
+            text1_adjusted_percent: adjusted_percent(&result.coverage1),
+            text2_adjusted_percent: adjusted_percent(&result.coverage2),
+            text1_ignored_percent: ignored_percent(t1_text),
+            text2_ignored_percent: ignored_percent(t2_text),
+            channel_percents: Vec::new(),
         })
     }
 
+    // If more than one channel was compared, show every channel's extent
+    // alongside each result so e.g. copied comments stand out next to the code.
+    // Channels without a result for the pair are shown as 0%
+    if texts.len() > 1 {
+        let mut channels: Vec<&'static str> = texts.keys().map(|channel| channel.name()).collect();
+        channels.sort_by_key(|name| *name != Channel::Content.name());
+        let mut pair_percents: HashMap<(TextOwnerID, TextOwnerID), Vec<HBChannelPercent>> =
+            HashMap::new();
+        for result in &plag_results {
+            let percents = pair_percents
+                .entry((result.owner_id1.clone(), result.owner_id2.clone()))
+                .or_insert_with(|| {
+                    channels
+                        .iter()
+                        .map(|channel| HBChannelPercent {
+                            channel,
+                            text1_plag_percent: 0,
+                            text2_plag_percent: 0,
+                        })
+                        .collect()
+                });
+            if let Some(percent) = percents
+                .iter_mut()
+                .find(|percent| percent.channel == result.channel)
+            {
+                percent.text1_plag_percent = result.text1_plag_percent;
+                percent.text2_plag_percent = result.text2_plag_percent;
+            }
+        }
+        for result in &mut plag_results {
+            result.channel_percents =
+                pair_percents[&(result.owner_id1.clone(), result.owner_id2.clone())].clone();
+        }
+    }
+
     // Create output directory and files, copy asserts over
     let mut source_template = File::open(TEMPLATE_PATH).unwrap_or_else(|_| {
         panic!(
@@ -145,21 +289,182 @@ pub fn output_results(
     });
 
     // Output report to html
+    let report = HBReport {
+        results: plag_results,
+        skipped_files,
+        common_fragments,
+        source_overlaps: overlaps
+            .iter()
+            .map(|overlap| HBSourceOverlap {
+                source_label1: manifest.label(&overlap.owner_id1),
+                source_label2: manifest.label(&overlap.owner_id2),
+                channel: overlap.channel.name(),
+                num_fragments: overlap.matching_fragments.len(),
+                source1_percent: overlap.coverage1.raw_percent as usize,
+                source2_percent: overlap.coverage2.raw_percent as usize,
+            })
+            .collect(),
+        attributions: attributions
+            .iter()
+            .map(|attribution| {
+                get_attribution(attribution, &texts[&attribution.channel][&attribution.owner], manifest)
+            })
+            .collect(),
+        settings,
+    };
     let hbars = Handlebars::new();
     hbars
-        .render_template_source_to_write(&mut source_template, &plag_results, &mut output_file)
+        .render_template_source_to_write(&mut source_template, &report, &mut output_file)
         .unwrap_or_else(|_| {
             panic!(
                 "Cannot render to template file without errors. JSON object: {:?}",
-                &plag_results
+                &report
             );
         });
 
     // Open the report using the OS-preferred method if possible
     if open_html_after && cfg!(target_os = "linux") {
         Command::new("xdg-open")
-            .args(&["./www/report.html"])
+            .args(["./www/report.html"])
             .output()
             .expect("Failed to execute xdg-open to open ./www/report.html!");
     }
 }
+
+/// Adjusted coverage percentage, if it differs from the raw percentage when shown
+fn adjusted_percent(coverage: &Coverage) -> Option<usize> {
+    let adjusted = coverage.adjusted_percent as usize;
+    if adjusted != coverage.raw_percent as usize {
+        Some(adjusted)
+    } else {
+        None
+    }
+}
+
+/// Percentage of the words of a text that were ignored
+fn ignored_percent(text: &CleanText) -> usize {
+    if text.words.is_empty() {
+        return 0;
+    }
+    ((text.ignored_word_count() as f32) / (text.words.len() as f32) * 100.0) as usize
+}
+
+/// Splits a text into one section per file (and per page, for files with pages),
+///     each with its plagiarized words in bold
+fn get_file_sections(text: &CleanText, text_intervals: &IntervalSet<usize>) -> Vec<HBFileSection> {
+    let ignored_intervals = text.ignored_locations.clone().to_interval_set();
+    get_section_bounds(text)
+        .into_iter()
+        .map(|(start, end, heading)| HBFileSection {
+            heading,
+            text: get_text_segments_from_intervals(
+                &text.words[start..end],
+                &section_intervals(text_intervals, start, end),
+                &section_intervals(&ignored_intervals, start, end),
+            ),
+        })
+        .collect()
+}
+
+/// Colours in the words of an untrusted text by the trusted source they are attributed to
+fn get_attribution(attribution: &Attribution, text: &CleanText, manifest: &Manifest) -> HBAttribution {
+    let source_class = |source_index: usize| format!("source-{}", source_index % NUM_SOURCE_COLORS);
+    let sections = get_section_bounds(text)
+        .into_iter()
+        .map(|(start, end, heading)| {
+            let mut segments: Vec<HBAttributedSegment> = Vec::new();
+            let mut cur_words: Vec<&str> = Vec::new();
+            let mut cur_source: Option<usize> = None;
+            for i in start..end {
+                let source = attribution.word_sources[i];
+                if source != cur_source && !cur_words.is_empty() {
+                    segments.push(HBAttributedSegment {
+                        text: cur_words.join(" "),
+                        source_class: cur_source.map(source_class),
+                    });
+                    cur_words = Vec::new();
+                }
+                cur_words.push(&text.words[i]);
+                cur_source = source;
+            }
+            if !cur_words.is_empty() {
+                segments.push(HBAttributedSegment {
+                    text: cur_words.join(" "),
+                    source_class: cur_source.map(source_class),
+                });
+            }
+            HBAttributedSection { heading, segments }
+        })
+        .collect();
+    HBAttribution {
+        owner_label: manifest.label(&attribution.owner),
+        channel: attribution.channel.name(),
+        total_percent: attribution.total.raw_percent as usize,
+        total_adjusted_percent: adjusted_percent(&attribution.total),
+        sources: attribution
+            .sources
+            .iter()
+            .enumerate()
+            .map(|(source_index, source)| HBAttributedSource {
+                label: manifest.label(&source.owner),
+                source_class: source_class(source_index),
+                percent: source.percent as usize,
+            })
+            .collect(),
+        text: sections,
+    }
+}
+
+/// Gets the (start, end (exclusive), heading) of each file (and each page, for
+///     files with pages) in a text. The heading names the file and/or page, and
+///     is only set if the text has more than one file or page.
+fn get_section_bounds(text: &CleanText) -> Vec<(usize, usize, Option<String>)> {
+    let multiple_files = text.file_starts.len() > 1;
+    let mut starts: Vec<usize> = text
+        .file_starts
+        .iter()
+        .map(|(_, start)| *start)
+        .chain(text.page_starts.iter().map(|(start, _)| *start))
+        .collect();
+    starts.sort_unstable();
+    starts.dedup();
+    let mut sections: Vec<(usize, usize, Option<String>)> = Vec::new();
+    for (i, start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(text.words.len());
+        if end <= *start {
+            continue;
+        }
+        let file_name = if multiple_files {
+            Some(text.file_at(*start).to_string())
+        } else {
+            None
+        };
+        let page = text.page_at(*start).map(|page| format!("page {}", page));
+        let heading = match (file_name, page) {
+            (Some(file_name), Some(page)) => Some(format!("{} \u{2014} {}", file_name, page)),
+            (file_name, page) => file_name.or(page),
+        };
+        sections.push((*start, end, heading));
+    }
+    sections
+}
+
+/// Gets the intervals within the words [start, end) of a text, shifted so that
+///     they start from 0
+fn section_intervals(
+    intervals: &IntervalSet<usize>,
+    start: usize,
+    end: usize,
+) -> IntervalSet<usize> {
+    let shifted: Vec<(usize, usize)> = intervals
+        .intersection(&(start, end - 1).to_interval_set())
+        .iter()
+        .map(|inter| {
+            (
+                bounded::Bounded::lower(inter) - start,
+                bounded::Bounded::upper(inter) - start,
+            )
+        })
+        .collect();
+    shifted.to_interval_set()
+}
@@ -1,7 +1,11 @@
-use crate::plagiarism_database::PlagiarismResult;
+use crate::attribution::Attribution;
+use crate::manifest::Manifest;
+use crate::plagiarism_database::{CommonFragment, PlagiarismResult};
+use crate::progress::Statistics;
+use crate::Findings;
 
 /// Print all untrusted results
-pub fn print_results_ut(results: &mut Vec<PlagiarismResult>) {
+pub fn print_results_ut(results: &mut Vec<PlagiarismResult>, manifest: &Manifest) {
     results.sort_by(|a, b| {
         b.matching_fragments
             .len()
@@ -11,15 +15,18 @@ pub fn print_results_ut(results: &mut Vec<PlagiarismResult>) {
     println!("\t===== BEGIN UNTRUSTED COMPARISON REPORT (Sorted by decreasing severity) ===== \n");
     for result in results {
         println!(
-            "\n\t REPORT: UNTRUSTED ID {} vs UNTRUSTED ID {}",
-            result.owner_id1, result.owner_id2
+            "\n\t REPORT: UNTRUSTED ID {} vs UNTRUSTED ID {} ({}{})",
+            manifest.label(&result.owner_id1),
+            manifest.label(&result.owner_id2),
+            result.channel.name(),
+            if result.same_group { ", same group" } else { "" }
         );
         print_result(result);
     }
     println!("\n\t===== END UNTRUSTED COMPARISON REPORT ===== \n");
 }
 
-pub fn print_results_t(results: &mut Vec<PlagiarismResult>) {
+pub fn print_results_t(results: &mut Vec<PlagiarismResult>, manifest: &Manifest) {
     results.sort_by(|a, b| {
         b.matching_fragments
             .len()
@@ -29,25 +36,186 @@ pub fn print_results_t(results: &mut Vec<PlagiarismResult>) {
     println!("\t**** BEGIN TRUSTED COMPARISON REPORT (Sorted by decreasing severity) **** \n");
     for result in results {
         println!(
-            "\n\t REPORT: TRUSTED ID {} vs UNTRUSTED ID {}",
-            result.owner_id1, result.owner_id2
+            "\n\t REPORT: TRUSTED ID {} vs UNTRUSTED ID {} ({})",
+            manifest.label(&result.owner_id1),
+            manifest.label(&result.owner_id2),
+            result.channel.name()
         );
         print_result(result);
     }
     println!("\n\t**** END TRUSTED COMPARISON REPORT **** \n");
 }
 
+/// Print results against submissions from past terms, if any
+pub fn print_results_archive(results: &mut Vec<PlagiarismResult>, manifest: &Manifest) {
+    if results.is_empty() {
+        return;
+    }
+    results.sort_by(|a, b| {
+        b.matching_fragments
+            .len()
+            .partial_cmp(&a.matching_fragments.len())
+            .unwrap()
+    });
+    println!("\t##### BEGIN ARCHIVE COMPARISON REPORT (Sorted by decreasing severity) ##### \n");
+    for result in results {
+        println!(
+            "\n\t REPORT: ARCHIVED ID {} from term {} vs UNTRUSTED ID {} ({})",
+            manifest.label(&result.owner_id1),
+            result.term1.as_deref().unwrap_or("unknown"),
+            manifest.label(&result.owner_id2),
+            result.channel.name()
+        );
+        print_result(result);
+    }
+    println!("\n\t##### END ARCHIVE COMPARISON REPORT ##### \n");
+}
+
+/// Print trusted sources that overlap with each other, if any
+pub fn print_results_overlap(results: &mut Vec<PlagiarismResult>, manifest: &Manifest) {
+    if results.is_empty() {
+        return;
+    }
+    results.sort_by(|a, b| {
+        b.matching_fragments
+            .len()
+            .partial_cmp(&a.matching_fragments.len())
+            .unwrap()
+    });
+    println!("\t~~~~~ BEGIN TRUSTED SOURCE OVERLAP REPORT (Sorted by decreasing severity) ~~~~~ \n");
+    for result in results {
+        println!(
+            "\n\t REPORT: TRUSTED ID {} vs TRUSTED ID {} ({})",
+            manifest.label(&result.owner_id1),
+            manifest.label(&result.owner_id2),
+            result.channel.name()
+        );
+        print_result(result);
+    }
+    println!("\n\t~~~~~ END TRUSTED SOURCE OVERLAP REPORT ~~~~~ \n");
+}
+
+/// Print how much of each untrusted text was found in each trusted source
+pub fn print_attributions(attributions: &[Attribution], manifest: &Manifest) {
+    if attributions.is_empty() {
+        return;
+    }
+    println!("\t+++++ BEGIN SOURCE ATTRIBUTION REPORT (Sorted by decreasing severity) +++++ \n");
+    for attribution in attributions {
+        let sources: Vec<String> = attribution
+            .sources
+            .iter()
+            .map(|source| format!("{} {:.0}%", manifest.label(&source.owner), source.percent))
+            .collect();
+        println!(
+            "UNTRUSTED ID {} ({}): {:.0}% found in trusted sources ({})",
+            manifest.label(&attribution.owner),
+            attribution.channel.name(),
+            attribution.total.raw_percent,
+            sources.join(", ")
+        );
+    }
+    println!("\n\t+++++ END SOURCE ATTRIBUTION REPORT +++++ \n");
+}
+
+/// Print a one-line summary of the findings
+pub fn print_summary(findings: &Findings, threshold: f32) {
+    println!(
+        "{} findings at or above {:.0}% ({} between submissions, {} from trusted sources, \
+         {} from past terms), highest extent {:.0}%",
+        findings.total(),
+        threshold,
+        findings.untrusted,
+        findings.trusted,
+        findings.archive,
+        findings.highest_percent
+    );
+}
+
+/// Print how much was checked and how long each phase of the check took
+pub fn print_statistics(statistics: &Statistics) {
+    println!("\t----- STATISTICS ----- \n");
+    println!("Files read: {}", statistics.files);
+    println!("Words: {}", statistics.words);
+    println!("Fragments: {}", statistics.fragments);
+    println!("Pairs compared: {}", statistics.pairs);
+    println!(
+        "Results: {} (with {} matching fragments)",
+        statistics.results, statistics.matching_fragments
+    );
+    for (phase, duration) in &statistics.timings {
+        println!("{}: {:.2}s", phase, duration.as_secs_f64());
+    }
+    println!("\n\t----- END STATISTICS ----- \n");
+}
+
+/// Print the settings the run was started with
+pub fn print_settings(config: &str) {
+    println!("\t----- SETTINGS ----- \n");
+    println!("{}", config.trim_end());
+    println!("\n\t----- END SETTINGS ----- \n");
+}
+
+/// Print the fragments that were ignored for being shared by too many submissions
+pub fn print_common_fragments(fragments: &[CommonFragment]) {
+    if fragments.is_empty() {
+        return;
+    }
+    println!("\t----- AUTOMATICALLY IGNORED BOILERPLATE ----- \n");
+    for fragment in fragments {
+        println!(
+            "Found in {} submissions ({}): {}",
+            fragment.texts,
+            fragment.channel.name(),
+            fragment.fragment
+        );
+    }
+    println!("\n\t----- END AUTOMATICALLY IGNORED BOILERPLATE ----- \n");
+}
+
 fn print_result(result: &PlagiarismResult) {
-    if result.equal_fragments {
-        for matching_fragment in &result.matching_fragments {
-            println!("Identical fragment detected: {}", matching_fragment.0)
-        }
-    } else {
-        for matching_fragment in &result.matching_fragments {
+    println!(
+        "Plagiarism extent: {:.0}% vs {:.0}% of all words ({:.0}% vs {:.0}% of checked words)",
+        result.coverage1.raw_percent,
+        result.coverage2.raw_percent,
+        result.coverage1.adjusted_percent,
+        result.coverage2.adjusted_percent
+    );
+    for ((matching_fragment, (file1, file2)), (page1, page2)) in result
+        .matching_fragments
+        .iter()
+        .zip(&result.matching_fragments_files)
+        .zip(&result.matching_fragments_pages)
+    {
+        // Only name the files if they aren't the whole submission
+        let files = if *file1 != result.owner_id1
+            || *file2 != result.owner_id2
+            || page1.is_some()
+            || page2.is_some()
+        {
+            format!(
+                " ({} vs {})",
+                file_with_page(file1, *page1),
+                file_with_page(file2, *page2)
+            )
+        } else {
+            String::new()
+        };
+        if result.equal_fragments {
+            println!("Identical fragment detected{}: {}", files, matching_fragment.0)
+        } else {
             println!(
-                "Similar fragments detected: {}\nVS\n{}",
-                matching_fragment.0, matching_fragment.1
+                "Similar fragments detected{}: {}\nVS\n{}",
+                files, matching_fragment.0, matching_fragment.1
             )
         }
     }
 }
+
+/// Formats a file name along with the page a match was found on, e.g. "report.pdf p.4"
+fn file_with_page(file_name: &str, page: Option<usize>) -> String {
+    match page {
+        Some(page) => format!("{} p.{}", file_name, page),
+        None => file_name.to_string(),
+    }
+}
@@ -0,0 +1,631 @@
+use crate::manifest::Manifest;
+use crate::plagiarism_database::{PlagiarismResult, TextSummary};
+use crate::AppSettings;
+#[cfg(feature = "sqlite")]
+use crate::{
+    incremental::CheckSettings,
+    plagiarism_database::{FragmentLocation, TextCategory},
+};
+#[cfg(feature = "sqlite")]
+use rusqlite::{params, Connection, OptionalExtension, Params, Row};
+#[cfg(feature = "sqlite")]
+use std::collections::HashMap;
+#[cfg(feature = "sqlite")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What to look up in a results database
+#[derive(Debug)]
+pub enum Query {
+    /// List every run
+    Runs,
+    /// List the pairs with the highest plagiarism percentages in a run (the latest if None)
+    TopPairs { run: Option<i64>, limit: usize },
+    /// Show every result (with its matches) involving a student, given by owner ID
+    ///     or the student ID in the manifest, in a run (the latest if None)
+    Student { student: String, run: Option<i64> },
+    /// Show the results that were added, removed or changed between two runs
+    Diff { run1: i64, run2: i64 },
+}
+
+/// Tables for runs, the submissions and settings of each run, and the
+///     pairwise results of each run with their matches
+#[cfg(feature = "sqlite")]
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY,
+        -- Seconds since the Unix epoch
+        created_at INTEGER NOT NULL,
+        untrusted_dir TEXT NOT NULL,
+        -- JSON object of the settings that change results
+        settings TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS submissions (
+        run_id INTEGER NOT NULL REFERENCES runs(id),
+        owner TEXT NOT NULL,
+        -- untrusted, trusted or archived
+        category TEXT NOT NULL,
+        term TEXT,
+        student_id TEXT,
+        name TEXT,
+        label TEXT NOT NULL,
+        words INTEGER NOT NULL,
+        PRIMARY KEY (run_id, owner, category)
+    );
+    CREATE TABLE IF NOT EXISTS results (
+        id INTEGER PRIMARY KEY,
+        run_id INTEGER NOT NULL REFERENCES runs(id),
+        channel TEXT NOT NULL,
+        -- Category of owner1. owner2 is always untrusted. Pairs of untrusted
+        -- owners are stored with owner1 < owner2 so runs can be compared
+        category1 TEXT NOT NULL,
+        owner1 TEXT NOT NULL,
+        owner2 TEXT NOT NULL,
+        term1 TEXT,
+        same_group INTEGER NOT NULL,
+        percent1 REAL NOT NULL,
+        percent2 REAL NOT NULL,
+        adjusted_percent1 REAL NOT NULL,
+        adjusted_percent2 REAL NOT NULL,
+        matches INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS results_by_run ON results (run_id);
+    CREATE TABLE IF NOT EXISTS matches (
+        result_id INTEGER NOT NULL REFERENCES results(id),
+        fragment1 TEXT NOT NULL,
+        fragment2 TEXT NOT NULL,
+        file1 TEXT NOT NULL,
+        file2 TEXT NOT NULL,
+        page1 INTEGER,
+        page2 INTEGER,
+        -- JSON arrays of [first word, last word] of each place the fragment is found
+        locations1 TEXT NOT NULL,
+        locations2 TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS matches_by_result ON matches (result_id);
+";
+
+/// Columns selected for a StoredResult, labelling each owner as in the manifest
+#[cfg(feature = "sqlite")]
+const RESULT_COLUMNS: &str = "r.id, r.channel, r.category1, r.owner1, r.owner2,
+    COALESCE(s1.label, r.owner1), COALESCE(s2.label, r.owner2), r.same_group,
+    r.percent1, r.percent2, r.matches
+    FROM results r
+    LEFT JOIN submissions s1
+        ON s1.run_id = r.run_id AND s1.owner = r.owner1 AND s1.category = r.category1
+    LEFT JOIN submissions s2
+        ON s2.run_id = r.run_id AND s2.owner = r.owner2 AND s2.category = 'untrusted'";
+
+/// A pairwise result as stored in a results database
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+struct StoredResult {
+    id: i64,
+    channel: String,
+    category1: String,
+    owner1: String,
+    owner2: String,
+    label1: String,
+    label2: String,
+    same_group: bool,
+    percent1: f64,
+    percent2: f64,
+    matches: i64,
+}
+
+#[cfg(feature = "sqlite")]
+impl StoredResult {
+    fn from_row(row: &Row) -> rusqlite::Result<StoredResult> {
+        Ok(StoredResult {
+            id: row.get(0)?,
+            channel: row.get(1)?,
+            category1: row.get(2)?,
+            owner1: row.get(3)?,
+            owner2: row.get(4)?,
+            label1: row.get(5)?,
+            label2: row.get(6)?,
+            same_group: row.get(7)?,
+            percent1: row.get(8)?,
+            percent2: row.get(9)?,
+            matches: row.get(10)?,
+        })
+    }
+
+    /// Identifies the same pair in different runs
+    fn key(&self) -> (String, String, String, String) {
+        (
+            self.channel.clone(),
+            self.category1.clone(),
+            self.owner1.clone(),
+            self.owner2.clone(),
+        )
+    }
+
+    /// Names the pair, e.g. "trusted book.txt vs untrusted alice.txt (content)".
+    ///     Archived owner IDs already start with their term.
+    fn describe(&self) -> String {
+        format!(
+            "{} {} vs untrusted {} ({}{})",
+            self.category1,
+            self.label1,
+            self.label2,
+            self.channel,
+            if self.same_group { ", same group" } else { "" }
+        )
+    }
+}
+
+/// Opens (or creates) a results database. Panics if it cannot be opened.
+#[cfg(feature = "sqlite")]
+fn open(path: &str) -> Connection {
+    let conn = Connection::open(path)
+        .unwrap_or_else(|err| panic!("Cannot open results database {}: {}", path, err));
+    conn.execute_batch(SCHEMA)
+        .unwrap_or_else(|err| panic!("Cannot create tables in results database {}: {}", path, err));
+    conn
+}
+
+/// Saves a run, its settings, the texts that were checked and all of its results
+///     to an SQLite database, which is created if it doesn't exist.
+///     Returns the ID of the run. Panics if the database cannot be written.
+#[cfg(feature = "sqlite")]
+pub fn save_run<'a>(
+    path: &str,
+    appsettings: &AppSettings,
+    manifest: &Manifest,
+    texts: &[TextSummary],
+    results: impl IntoIterator<Item = &'a PlagiarismResult>,
+) -> i64 {
+    let settings = serde_json::to_string(&CheckSettings::from_app_settings(appsettings, manifest))
+        .expect("Settings could not be converted to JSON!");
+    let mut conn = open(path);
+    insert_run(
+        &mut conn,
+        &appsettings.udir,
+        &settings,
+        manifest,
+        texts,
+        results,
+    )
+    .unwrap_or_else(|err| panic!("Cannot save results to {}: {}", path, err))
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn save_run<'a>(
+    _path: &str,
+    _appsettings: &AppSettings,
+    _manifest: &Manifest,
+    _texts: &[TextSummary],
+    _results: impl IntoIterator<Item = &'a PlagiarismResult>,
+) -> i64 {
+    panic!("This build does not support saving results to an SQLite database");
+}
+
+#[cfg(feature = "sqlite")]
+fn insert_run<'a>(
+    conn: &mut Connection,
+    untrusted_dir: &str,
+    settings: &str,
+    manifest: &Manifest,
+    texts: &[TextSummary],
+    results: impl IntoIterator<Item = &'a PlagiarismResult>,
+) -> rusqlite::Result<i64> {
+    let tx = conn.transaction()?;
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0);
+    tx.execute(
+        "INSERT INTO runs (created_at, untrusted_dir, settings) VALUES (?1, ?2, ?3)",
+        params![created_at, untrusted_dir, settings],
+    )?;
+    let run_id = tx.last_insert_rowid();
+
+    for text in texts {
+        let info = manifest.get(&text.owner);
+        tx.execute(
+            "INSERT INTO submissions (run_id, owner, category, term, student_id, name, label, words)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                run_id,
+                text.owner,
+                text.category.name(),
+                text.term,
+                info.map(|info| &info.student_id),
+                info.and_then(|info| info.name.as_ref()),
+                manifest.label(&text.owner),
+                text.words as i64
+            ],
+        )?;
+    }
+
+    let to_json = |locations: &[FragmentLocation]| {
+        serde_json::to_string(locations).expect("Locations could not be converted to JSON!")
+    };
+    for result in results {
+        // Untrusted pairs are checked in no particular order, so put them in one
+        let swap =
+            result.category1() == TextCategory::Untrusted && result.owner_id1 > result.owner_id2;
+        let order = |pair: (String, String)| if swap { (pair.1, pair.0) } else { pair };
+        let (owner1, owner2) = order((result.owner_id1.clone(), result.owner_id2.clone()));
+        let (coverage1, coverage2) = if swap {
+            (result.coverage2, result.coverage1)
+        } else {
+            (result.coverage1, result.coverage2)
+        };
+        tx.execute(
+            "INSERT INTO results (run_id, channel, category1, owner1, owner2, term1, same_group,
+                percent1, percent2, adjusted_percent1, adjusted_percent2, matches)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                run_id,
+                result.channel.name(),
+                result.category1().name(),
+                owner1,
+                owner2,
+                result.term1,
+                result.same_group,
+                coverage1.raw_percent as f64,
+                coverage2.raw_percent as f64,
+                coverage1.adjusted_percent as f64,
+                coverage2.adjusted_percent as f64,
+                result.matching_fragments.len() as i64
+            ],
+        )?;
+        let result_id = tx.last_insert_rowid();
+        for (((fragments, locations), files), pages) in result
+            .matching_fragments
+            .iter()
+            .zip(&result.matching_fragments_locations)
+            .zip(&result.matching_fragments_files)
+            .zip(&result.matching_fragments_pages)
+        {
+            let (fragment1, fragment2) = order(fragments.clone());
+            let (locations1, locations2) = order((to_json(&locations.0), to_json(&locations.1)));
+            let (file1, file2) = order(files.clone());
+            let (page1, page2) = if swap { (pages.1, pages.0) } else { *pages };
+            tx.execute(
+                "INSERT INTO matches (result_id, fragment1, fragment2, file1, file2, page1, page2,
+                    locations1, locations2)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    result_id,
+                    fragment1,
+                    fragment2,
+                    file1,
+                    file2,
+                    page1.map(|page| page as i64),
+                    page2.map(|page| page as i64),
+                    locations1,
+                    locations2
+                ],
+            )?;
+        }
+    }
+    tx.commit()?;
+    Ok(run_id)
+}
+
+/// Gets the results matching a condition on the results table (aliased r)
+#[cfg(feature = "sqlite")]
+fn select_results<P: Params>(
+    conn: &Connection,
+    condition: &str,
+    params: P,
+) -> rusqlite::Result<Vec<StoredResult>> {
+    let sql = format!("SELECT {} WHERE {}", RESULT_COLUMNS, condition);
+    let mut statement = conn.prepare(&sql)?;
+    let results = statement.query_map(params, StoredResult::from_row)?;
+    results.collect()
+}
+
+/// Gets the given run, or the latest run if None
+#[cfg(feature = "sqlite")]
+fn find_run(conn: &Connection, run: Option<i64>) -> rusqlite::Result<Option<i64>> {
+    match run {
+        Some(run) => conn
+            .query_row("SELECT id FROM runs WHERE id = ?1", [run], |row| row.get(0))
+            .optional(),
+        None => conn.query_row("SELECT MAX(id) FROM runs", [], |row| row.get(0)),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn top_pairs(conn: &Connection, run: i64, limit: usize) -> rusqlite::Result<Vec<StoredResult>> {
+    select_results(
+        conn,
+        "r.run_id = ?1 ORDER BY MAX(r.percent1, r.percent2) DESC, r.id LIMIT ?2",
+        params![run, limit as i64],
+    )
+}
+
+/// Results involving any submission with the given owner ID or student ID
+#[cfg(feature = "sqlite")]
+fn student_results(
+    conn: &Connection,
+    run: i64,
+    student: &str,
+) -> rusqlite::Result<Vec<StoredResult>> {
+    select_results(
+        conn,
+        "r.run_id = ?1 AND (r.owner1 = ?2 OR r.owner2 = ?2
+            OR s1.student_id = ?2 OR s2.student_id = ?2)
+         ORDER BY MAX(r.percent1, r.percent2) DESC, r.id",
+        params![run, student],
+    )
+}
+
+/// Matches of a result as (fragment1, fragment2, file1, file2, page1, page2)
+#[cfg(feature = "sqlite")]
+type StoredMatch = (String, String, String, String, Option<i64>, Option<i64>);
+
+#[cfg(feature = "sqlite")]
+fn result_matches(conn: &Connection, result_id: i64) -> rusqlite::Result<Vec<StoredMatch>> {
+    let mut statement = conn.prepare(
+        "SELECT fragment1, fragment2, file1, file2, page1, page2 FROM matches
+         WHERE result_id = ?1 ORDER BY rowid",
+    )?;
+    let matches = statement.query_map([result_id], |row| {
+        Ok((
+            row.get(0)?,
+            row.get(1)?,
+            row.get(2)?,
+            row.get(3)?,
+            row.get(4)?,
+            row.get(5)?,
+        ))
+    })?;
+    matches.collect()
+}
+
+/// Results that are only in the second run, only in the first run, and
+///     in both runs but with different (rounded) percentages
+#[cfg(feature = "sqlite")]
+type RunDiff = (
+    Vec<StoredResult>,
+    Vec<StoredResult>,
+    Vec<(StoredResult, StoredResult)>,
+);
+
+#[cfg(feature = "sqlite")]
+fn diff_runs(conn: &Connection, run1: i64, run2: i64) -> rusqlite::Result<RunDiff> {
+    let order = "ORDER BY r.channel, r.category1, r.owner1, r.owner2";
+    let condition = format!("r.run_id = ?1 {}", order);
+    let mut old_results: HashMap<_, StoredResult> = select_results(conn, &condition, [run1])?
+        .into_iter()
+        .map(|result| (result.key(), result))
+        .collect();
+    let mut added: Vec<StoredResult> = Vec::new();
+    let mut changed: Vec<(StoredResult, StoredResult)> = Vec::new();
+    for result in select_results(conn, &condition, [run2])? {
+        match old_results.remove(&result.key()) {
+            None => added.push(result),
+            Some(old) => {
+                if old.percent1.round() != result.percent1.round()
+                    || old.percent2.round() != result.percent2.round()
+                {
+                    changed.push((old, result));
+                }
+            }
+        }
+    }
+    let mut removed: Vec<StoredResult> = old_results.into_values().collect();
+    removed.sort_by_key(|result| result.key());
+    Ok((added, removed, changed))
+}
+
+/// Looks something up in a results database and prints it.
+///     Panics if the database cannot be read or the run does not exist.
+#[cfg(feature = "sqlite")]
+pub fn run_query(path: &str, query: &Query) {
+    let conn = open(path);
+    let fail =
+        |err: rusqlite::Error| -> ! { panic!("Cannot read results database {}: {}", path, err) };
+    let get_run = |run: Option<i64>| match find_run(&conn, run).unwrap_or_else(|err| fail(err)) {
+        Some(run) => run,
+        None => match run {
+            Some(run) => panic!("There is no run {} in {}", run, path),
+            None => panic!("There are no runs in {}", path),
+        },
+    };
+    match query {
+        Query::Runs => {
+            let mut statement = conn
+                .prepare(
+                    "SELECT r.id, datetime(r.created_at, 'unixepoch'), r.untrusted_dir,
+                        (SELECT COUNT(*) FROM submissions s
+                         WHERE s.run_id = r.id AND s.category = 'untrusted'),
+                        (SELECT COUNT(*) FROM results x WHERE x.run_id = r.id)
+                     FROM runs r ORDER BY r.id",
+                )
+                .unwrap_or_else(|err| fail(err));
+            let runs = statement
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, i64>(3)?,
+                        row.get::<_, i64>(4)?,
+                    ))
+                })
+                .and_then(|runs| runs.collect::<rusqlite::Result<Vec<_>>>())
+                .unwrap_or_else(|err| fail(err));
+            for (id, created_at, untrusted_dir, submissions, results) in runs {
+                println!(
+                    "Run {} at {} UTC: {} ({} untrusted submissions, {} results)",
+                    id, created_at, untrusted_dir, submissions, results
+                );
+            }
+        }
+        Query::TopPairs { run, limit } => {
+            let run = get_run(*run);
+            println!("Top pairs of run {}:", run);
+            for result in top_pairs(&conn, run, *limit).unwrap_or_else(|err| fail(err)) {
+                println!(
+                    "{:>4.0}% vs {:>4.0}%  {}",
+                    result.percent1,
+                    result.percent2,
+                    result.describe()
+                );
+            }
+        }
+        Query::Student { student, run } => {
+            let run = get_run(*run);
+            let results = student_results(&conn, run, student).unwrap_or_else(|err| fail(err));
+            if results.is_empty() {
+                println!("No results involving {} in run {}", student, run);
+            }
+            for result in results {
+                println!(
+                    "\n{} ({:.0}% vs {:.0}%, {} matching fragments)",
+                    result.describe(),
+                    result.percent1,
+                    result.percent2,
+                    result.matches
+                );
+                let matches = result_matches(&conn, result.id).unwrap_or_else(|err| fail(err));
+                for (fragment1, fragment2, file1, file2, page1, page2) in matches {
+                    let with_page = |file: String, page: Option<i64>| match page {
+                        Some(page) => format!("{} p.{}", file, page),
+                        None => file,
+                    };
+                    let fragments = if fragment1 == fragment2 {
+                        fragment1
+                    } else {
+                        format!("{} | {}", fragment1, fragment2)
+                    };
+                    println!(
+                        "  {} ({} vs {})",
+                        fragments,
+                        with_page(file1, page1),
+                        with_page(file2, page2)
+                    );
+                }
+            }
+        }
+        Query::Diff { run1, run2 } => {
+            let (run1, run2) = (get_run(Some(*run1)), get_run(Some(*run2)));
+            let (added, removed, changed) =
+                diff_runs(&conn, run1, run2).unwrap_or_else(|err| fail(err));
+            println!("Only in run {}:", run2);
+            for result in added {
+                println!(
+                    "  {:.0}% vs {:.0}%  {}",
+                    result.percent1,
+                    result.percent2,
+                    result.describe()
+                );
+            }
+            println!("Only in run {}:", run1);
+            for result in removed {
+                println!(
+                    "  {:.0}% vs {:.0}%  {}",
+                    result.percent1,
+                    result.percent2,
+                    result.describe()
+                );
+            }
+            println!("Changed between runs {} and {}:", run1, run2);
+            for (old, new) in changed {
+                println!(
+                    "  {:.0}% vs {:.0}% -> {:.0}% vs {:.0}%  {}",
+                    old.percent1,
+                    old.percent2,
+                    new.percent1,
+                    new.percent2,
+                    new.describe()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn run_query(_path: &str, _query: &Query) {
+    panic!("This build does not support querying an SQLite results database");
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::file_utils::Language;
+    use crate::plagiarism_database::PlagiarismDatabase;
+    use crate::Metric;
+
+    /// Checks the texts and saves the results as a new run
+    fn save_texts(conn: &mut Connection, texts: &[(&str, &str)]) -> i64 {
+        let mut db = PlagiarismDatabase::new(3, 0, Metric::Equal, Vec::new());
+        for (owner, text) in texts {
+            let files = vec![(owner.to_string(), text.to_string())];
+            db.add_untrusted_submission(owner, &files, Language::Text);
+        }
+        let results = db.check_untrusted_plagiarism();
+        let manifest = Manifest::default();
+        insert_run(
+            conn,
+            "ut",
+            "{}",
+            &manifest,
+            &db.get_text_summaries(),
+            &results,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_store_and_query_runs() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        let run1 = save_texts(
+            &mut conn,
+            &[
+                ("bob.txt", "the quick brown fox jumps over the lazy dog"),
+                ("alice.txt", "the quick brown fox jumps over the lazy dog"),
+            ],
+        );
+        let run2 = save_texts(
+            &mut conn,
+            &[
+                ("bob.txt", "the quick brown fox jumps over the lazy dog"),
+                ("alice.txt", "the quick brown fox jumps over a sleepy cat"),
+                ("carol.txt", "the quick brown fox jumps over the lazy dog"),
+            ],
+        );
+        assert_eq!(find_run(&conn, None).unwrap(), Some(run2));
+        assert_eq!(find_run(&conn, Some(run2 + 1)).unwrap(), None);
+
+        let top = top_pairs(&conn, run2, 1).unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(
+            (top[0].owner1.as_str(), top[0].owner2.as_str()),
+            ("bob.txt", "carol.txt")
+        );
+        assert_eq!(top[0].percent1, 100.0);
+
+        let alice = student_results(&conn, run1, "alice.txt").unwrap();
+        assert_eq!(alice.len(), 1);
+        // Untrusted pairs are stored in order of owner ID
+        assert_eq!(alice[0].owner1, "alice.txt");
+        let matches = result_matches(&conn, alice[0].id).unwrap();
+        assert_eq!(matches.len(), 7);
+        assert_eq!(matches[0].2, "alice.txt");
+
+        let (added, removed, changed) = diff_runs(&conn, run1, run2).unwrap();
+        let owners = |results: &[StoredResult]| -> Vec<(String, String)> {
+            results
+                .iter()
+                .map(|result| (result.owner1.clone(), result.owner2.clone()))
+                .collect()
+        };
+        assert_eq!(
+            owners(&added),
+            vec![
+                ("alice.txt".to_string(), "carol.txt".to_string()),
+                ("bob.txt".to_string(), "carol.txt".to_string())
+            ]
+        );
+        assert!(removed.is_empty());
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].1.owner2, "bob.txt");
+    }
+}
@@ -3,10 +3,11 @@ use gcollections::ops::*;
 use interval::interval_set::*;
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::collections::HashSet;
 
 /// Extracts lists of consecutive words of list length n from the provided text.
 ///     Cleans the text first.
-pub fn extract_clean_word_ngrams(words: &Vec<String>, n: usize) -> Vec<String> {
+pub fn extract_clean_word_ngrams(words: &[String], n: usize) -> Vec<String> {
     let mut output = Vec::new();
     // No way to find plagiarism if chunk size > # words
     if n > words.len() {
@@ -24,6 +25,31 @@ pub fn extract_clean_word_ngrams(words: &Vec<String>, n: usize) -> Vec<String> {
     output
 }
 
+/// Common English words that carry little meaning on their own
+const STOPWORDS: [&str; 100] = [
+    "a", "about", "after", "all", "also", "am", "an", "and", "any", "are", "as", "at", "be",
+    "because", "been", "before", "being", "between", "both", "but", "by", "can", "could", "did",
+    "do", "does", "each", "for", "from", "had", "has", "have", "he", "her", "his", "how", "i",
+    "if", "in", "into", "is", "it", "its", "just", "may", "more", "most", "much", "must", "my",
+    "no", "not", "now", "of", "on", "one", "only", "or", "other", "our", "out", "over", "same",
+    "she", "should", "so", "some", "such", "than", "that", "the", "their", "them", "then", "there",
+    "these", "they", "this", "those", "through", "to", "too", "under", "up", "very", "was", "we",
+    "were", "what", "when", "where", "which", "while", "who", "why", "will", "with", "would",
+    "you", "your",
+];
+
+/// Checks if a cleaned (lowercase) word is a common English stopword
+pub fn is_stopword(word: &str) -> bool {
+    lazy_static! {
+        static ref STOPWORD_SET: HashSet<&'static str> = STOPWORDS.iter().copied().collect();
+    }
+    STOPWORD_SET.contains(word)
+}
+
+/// Version of the normalization done by clean_text. Must be increased whenever
+///     clean_text changes, so that indexes of texts cleaned the old way are rejected
+pub const NORMALIZATION_VERSION: u32 = 1;
+
 /// Removes nonalphanumeric characters, redundant spaces, newlines,
 ///     converts to lowecase and trims text
 pub fn clean_text(text: &str) -> Vec<String> {
@@ -40,72 +66,47 @@ pub fn clean_text(text: &str) -> Vec<String> {
     new_text = REMOVE_NONALPHA.replace_all(&new_text, " ").to_string();
     REMOVE_SPACES
         .replace_all(&new_text, " ")
-        .trim()
         .split_whitespace()
         .map(String::from)
         .collect()
 }
 
-/// Given a list of words and the intervals (union-ed) that are plagiarized:
-///     Separate the words into text segments where plagiarized segments are indicated
-///     in bold.
+/// Given a list of words, the intervals (union-ed) that are plagiarized and the
+///     intervals that were ignored: Separate the words into text segments where
+///     plagiarized segments are indicated in bold, and ignored segments (that
+///     are not also plagiarized) are indicated as ignored.
 /// Algorithm:
-///     - Since the intervals are sorted, we read each interval one by one from start
-///     -
-pub fn get_boldtext_segments_from_intervals(
-    words: &Vec<String>,
+///     - Each run of consecutive words with the same formatting becomes one segment
+pub fn get_text_segments_from_intervals(
+    words: &[String],
     text_intervals: &IntervalSet<usize>,
+    ignored_intervals: &IntervalSet<usize>,
 ) -> Vec<TextMaybeBold> {
     let mut text_segments: Vec<TextMaybeBold> = Vec::new();
     let mut cur_words: Vec<String> = Vec::new();
-    let mut contains_previously = false;
-
-    for (i, item) in words.iter().enumerate() {
-        let word = item.clone();
-        if text_intervals.contains(&i) {
-            // In interval, should be bold
-            if contains_previously {
-                cur_words.push(word);
-            } else {
-                if !cur_words.is_empty() {
-                    text_segments.push(TextMaybeBold {
-                        text: cur_words.join(" "),
-                        is_bold: false,
-                    });
-                }
-                cur_words = Vec::new();
-                cur_words.push(word);
-            }
-            contains_previously = true;
-        } else {
-            // Not in interval now
-            if contains_previously {
-                if !cur_words.is_empty() {
-                    text_segments.push(TextMaybeBold {
-                        text: cur_words.join(" "),
-                        is_bold: true,
-                    });
-                }
-                cur_words = Vec::new();
-                cur_words.push(word);
-            } else {
-                cur_words.push(word);
-            }
-            contains_previously = false;
-        }
-    }
-    if !cur_words.is_empty() {
-        if contains_previously {
+    // (is_bold, is_ignored) of the words in cur_words
+    let mut cur_format = (false, false);
+
+    for (i, word) in words.iter().enumerate() {
+        let is_bold = text_intervals.contains(&i);
+        let format = (is_bold, !is_bold && ignored_intervals.contains(&i));
+        if format != cur_format && !cur_words.is_empty() {
             text_segments.push(TextMaybeBold {
                 text: cur_words.join(" "),
-                is_bold: true,
-            });
-        } else {
-            text_segments.push(TextMaybeBold {
-                text: cur_words.join(" "),
-                is_bold: false,
+                is_bold: cur_format.0,
+                is_ignored: cur_format.1,
             });
+            cur_words = Vec::new();
         }
+        cur_words.push(word.clone());
+        cur_format = format;
+    }
+    if !cur_words.is_empty() {
+        text_segments.push(TextMaybeBold {
+            text: cur_words.join(" "),
+            is_bold: cur_format.0,
+            is_ignored: cur_format.1,
+        });
     }
     text_segments
 }
@@ -118,7 +119,7 @@ mod tests {
     fn test_ngram() {
         assert_eq!(
             extract_clean_word_ngrams(
-                &vec!["mary".to_string(), "had".to_string(), "a".to_string()],
+                &["mary".to_string(), "had".to_string(), "a".to_string()],
                 2
             ),
             vec!["mary had", "had a"]
@@ -140,21 +141,23 @@ mod tests {
 
     #[test]
     fn test_intervals_firstwords_bold() {
-        let words = vec!["a", "b", "c", "d", "e"]
+        let words: Vec<String> = ["a", "b", "c", "d", "e"]
             .iter()
             .map(|x| x.to_string())
             .collect();
         let intervals = vec![(0, 1)].to_interval_set();
         assert_eq!(
-            get_boldtext_segments_from_intervals(&words, &intervals),
+            get_text_segments_from_intervals(&words, &intervals, &IntervalSet::empty()),
             vec![
                 TextMaybeBold {
                     text: "a b".to_string(),
-                    is_bold: true
+                    is_bold: true,
+                    is_ignored: false
                 },
                 TextMaybeBold {
                     text: "c d e".to_string(),
-                    is_bold: false
+                    is_bold: false,
+                    is_ignored: false
                 }
             ]
         );
@@ -162,21 +165,23 @@ mod tests {
 
     #[test]
     fn test_intervals_lastwords_bold() {
-        let words = vec!["a", "b", "c", "d", "e"]
+        let words: Vec<String> = ["a", "b", "c", "d", "e"]
             .iter()
             .map(|x| x.to_string())
             .collect();
         let intervals = vec![(2, 4)].to_interval_set();
         assert_eq!(
-            get_boldtext_segments_from_intervals(&words, &intervals),
+            get_text_segments_from_intervals(&words, &intervals, &IntervalSet::empty()),
             vec![
                 TextMaybeBold {
                     text: "a b".to_string(),
-                    is_bold: false
+                    is_bold: false,
+                    is_ignored: false
                 },
                 TextMaybeBold {
                     text: "c d e".to_string(),
-                    is_bold: true
+                    is_bold: true,
+                    is_ignored: false
                 }
             ]
         );
@@ -184,63 +189,93 @@ mod tests {
 
     #[test]
     fn test_intervals_no_bold() {
-        let words = vec!["a", "b", "c", "d", "e"]
+        let words: Vec<String> = ["a", "b", "c", "d", "e"]
             .iter()
             .map(|x| x.to_string())
             .collect();
         let intervals = vec![].to_interval_set();
         assert_eq!(
-            get_boldtext_segments_from_intervals(&words, &intervals),
+            get_text_segments_from_intervals(&words, &intervals, &IntervalSet::empty()),
             vec![TextMaybeBold {
                 text: "a b c d e".to_string(),
-                is_bold: false
+                is_bold: false,
+                is_ignored: false
             },]
         );
     }
 
     #[test]
     fn test_intervals_all_bold() {
-        let words = vec!["a", "b", "c", "d", "e"]
+        let words: Vec<String> = ["a", "b", "c", "d", "e"]
             .iter()
             .map(|x| x.to_string())
             .collect();
         let intervals = vec![(0, 4)].to_interval_set();
         assert_eq!(
-            get_boldtext_segments_from_intervals(&words, &intervals),
+            get_text_segments_from_intervals(&words, &intervals, &IntervalSet::empty()),
             vec![TextMaybeBold {
                 text: "a b c d e".to_string(),
-                is_bold: true
+                is_bold: true,
+                is_ignored: false
             },]
         );
     }
 
     #[test]
     fn test_intervals_single_letters_bold() {
-        let words = vec!["a", "b", "c", "d", "e"]
+        let words: Vec<String> = ["a", "b", "c", "d", "e"]
             .iter()
             .map(|x| x.to_string())
             .collect();
         let intervals = vec![(0, 0), (2, 2)].to_interval_set();
         assert_eq!(
-            get_boldtext_segments_from_intervals(&words, &intervals),
+            get_text_segments_from_intervals(&words, &intervals, &IntervalSet::empty()),
             vec![
                 TextMaybeBold {
                     text: "a".to_string(),
-                    is_bold: true
+                    is_bold: true,
+                    is_ignored: false
                 },
                 TextMaybeBold {
                     text: "b".to_string(),
-                    is_bold: false
+                    is_bold: false,
+                    is_ignored: false
                 },
                 TextMaybeBold {
                     text: "c".to_string(),
-                    is_bold: true
+                    is_bold: true,
+                    is_ignored: false
                 },
                 TextMaybeBold {
                     text: "d e".to_string(),
-                    is_bold: false
+                    is_bold: false,
+                    is_ignored: false
                 },
             ]
         );
     }
+
+    #[test]
+    fn test_intervals_ignored() {
+        let words: Vec<String> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let intervals = vec![(1, 1)].to_interval_set();
+        let ignored = vec![(0, 2)].to_interval_set();
+        let segment = |text: &str, is_bold: bool, is_ignored: bool| TextMaybeBold {
+            text: text.to_string(),
+            is_bold,
+            is_ignored,
+        };
+        assert_eq!(
+            get_text_segments_from_intervals(&words, &intervals, &ignored),
+            vec![
+                segment("a", false, true),
+                segment("b", true, false),
+                segment("c", false, true),
+                segment("d e", false, false),
+            ]
+        );
+    }
 }